@@ -0,0 +1,24 @@
+#![no_main]
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+use libfuzzer_sys::fuzz_target;
+
+use fortunate_json::fortunate_json::{decode, decode_borrowed};
+
+fuzz_target!(|data: &[u8]| {
+    let s = match std::str::from_utf8(data) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+
+    let _ = decode::<HashMap<String, Vec<Option<i64>>>>(s);
+    let _ = decode::<BTreeMap<String, Vec<String>>>(s);
+    let _ = decode::<Vec<HashMap<String, f64>>>(s);
+    let _ = decode::<Option<Vec<u32>>>(s);
+    let _ = decode::<Vec<Vec<Option<f32>>>>(s);
+
+    let _ = decode_borrowed::<HashMap<String, Vec<Cow<str>>>>(s);
+    let _ = decode_borrowed::<Vec<Option<&str>>>(s);
+});
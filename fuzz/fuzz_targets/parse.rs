@@ -0,0 +1,91 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use fortunate_json::fortunate_json::{
+    parse_borrowed, parse_borrowed_with, parse_bytes_with, parse_recovering, DuplicateKeys,
+    LoneSurrogates, ParseOptions, PushParser, Reader, Syntax,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_bytes_with(data, &ParseOptions::default());
+    let _ = parse_bytes_with(
+        data,
+        &ParseOptions {
+            syntax: Syntax::Strict,
+            duplicate_keys: DuplicateKeys::Error,
+            ..Default::default()
+        },
+    );
+    let _ = parse_bytes_with(
+        data,
+        &ParseOptions {
+            lone_surrogates: LoneSurrogates::Preserve,
+            duplicate_keys: DuplicateKeys::CollectAll,
+            max_depth: None,
+            ..Default::default()
+        },
+    );
+    let _ = parse_bytes_with(
+        data,
+        &ParseOptions {
+            syntax: Syntax::Relaxed,
+            ..Default::default()
+        },
+    );
+    let _ = parse_bytes_with(
+        data,
+        &ParseOptions {
+            lone_surrogates: LoneSurrogates::Replace,
+            duplicate_keys: DuplicateKeys::FirstWins,
+            max_string_len: Some(8),
+            max_array_len: Some(4),
+            max_object_len: Some(4),
+            max_nodes: Some(16),
+            ..Default::default()
+        },
+    );
+
+    for event in Reader::new(data) {
+        if event.is_err() {
+            break;
+        }
+    }
+
+    // Parsing and dropping don't recurse, so these can go without a depth limit.
+    let mut push = PushParser::with_options(ParseOptions {
+        syntax: Syntax::Relaxed,
+        duplicate_keys: DuplicateKeys::Error,
+        max_depth: None,
+        max_string_len: Some(8),
+        ..Default::default()
+    });
+    for chunk in data.chunks(3) {
+        push.feed(chunk);
+        while let Ok(Some(_)) = push.next_event() {}
+    }
+    push.finish();
+    while let Ok(Some(_)) = push.next_event() {}
+
+    if let Ok(s) = std::str::from_utf8(data) {
+        let _ = parse_borrowed(s);
+        let _ = parse_borrowed_with(
+            s,
+            &ParseOptions {
+                lone_surrogates: LoneSurrogates::Preserve,
+                duplicate_keys: DuplicateKeys::CollectAll,
+                max_depth: None,
+                ..Default::default()
+            },
+        );
+        let _ = parse_recovering(s, &ParseOptions::default());
+        let _ = parse_recovering(
+            s,
+            &ParseOptions {
+                syntax: Syntax::Relaxed,
+                max_depth: Some(4),
+                ..Default::default()
+            },
+        );
+    }
+});
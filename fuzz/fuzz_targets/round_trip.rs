@@ -0,0 +1,28 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use fortunate_json::fortunate_json::{
+    parse_bytes_with, parse_with, to_string, to_string_pretty, LoneSurrogates, ParseOptions,
+    PrettyConfig,
+};
+
+// Anything that parses must serialize to JSON that parses back to the same value.
+fuzz_target!(|data: &[u8]| {
+    // Serializing and comparing recurse, so the default depth limit stays on.  Otherwise deep
+    // input would be reported as a stack overflow.
+    let options = ParseOptions {
+        lone_surrogates: LoneSurrogates::Preserve,
+        ..Default::default()
+    };
+    let v = match parse_bytes_with(data, &options) {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+
+    let compact = to_string(&v);
+    assert_eq!(v, parse_with(&compact, &options).unwrap());
+
+    let pretty = to_string_pretty(&v, &PrettyConfig::default());
+    assert_eq!(v, parse_with(&pretty, &options).unwrap());
+});
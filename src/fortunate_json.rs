@@ -1,36 +1,161 @@
+pub mod borrowed;
+pub mod index;
+pub mod jsonpath;
+pub mod map;
+pub mod merge;
+pub mod ndjson;
+pub mod number;
 pub mod parse;
+pub mod patch;
+pub mod pointer;
+pub mod pretty;
+pub mod reader;
+pub mod recover;
+pub mod sequence;
+pub mod serialize;
+pub mod writer;
 
-use parse::ParseError;
 use std::collections::hash_map::HashMap;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::hash::Hash;
 use std::str::FromStr;
 
-pub use parse::parse;
-
-#[derive(Debug, PartialEq)]
+pub use borrowed::{
+    decode_borrowed, decode_borrowed_with, extract_borrowed_field, parse_borrowed,
+    parse_borrowed_with, BorrowedValue, FromBorrowedJSON,
+};
+pub use index::ValueIndex;
+pub use jsonpath::{JsonPath, JsonPathError, Node};
+pub use map::Map;
+pub use merge::{merge_all, merge_diff, ArrayMerge, MergeOptions};
+pub use ndjson::{NdjsonError, NdjsonReader, NdjsonWriter};
+pub use number::Number;
+pub use parse::{
+    parse, parse_bytes_with, parse_with, DuplicateKeys, LoneSurrogates, ParseError, ParseErrorKind,
+    ParseOptions, Position, Syntax,
+};
+pub use patch::{diff, Patch, PatchError, PatchErrorKind, PatchOperation};
+pub use pointer::{JsonPointer, PointerError, PointerErrorKind};
+pub use pretty::{to_string_pretty, Indent, PrettyConfig};
+pub use reader::{Event, PushParser, Reader};
+pub use recover::{parse_recovering, Diagnostic, Recovered, Span};
+pub use sequence::{Concatenated, JsonSeqReader, JsonSeqWriter};
+pub use serialize::to_string;
+pub use writer::{JsonWriter, WriterError};
+
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum Value {
+    #[default]
     Null,
     Boolean(bool),
-    Number(f32),
+    Number(Number),
     String(String),
+    /// A string with lone surrogates in it, encoded as WTF-8.  Only produced when parsing with
+    /// LoneSurrogates::Preserve, and written back out with the surrogates escaped.
+    RawString(Vec<u8>),
     Array(Vec<Value>),
-    Object(HashMap<String, Value>),
+    Object(Map),
+}
+
+// The default drop would recurse once per level of nesting, so a deep enough document would
+// overflow the stack.  Instead, children are moved out onto a list and dropped once they are
+// empty.
+impl Drop for Value {
+    fn drop(&mut self) {
+        let mut pending = match self {
+            Value::Array(a) if !a.is_empty() => std::mem::take(a),
+            Value::Object(o) if !o.is_empty() => {
+                std::mem::take(o).into_iter().map(|(_, v)| v).collect()
+            }
+            _ => return,
+        };
+
+        while let Some(mut v) = pending.pop() {
+            match &mut v {
+                Value::Array(a) => pending.append(a),
+                Value::Object(o) => pending.extend(std::mem::take(o).into_iter().map(|(_, v)| v)),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Null,
+    Boolean,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ValueKind::Null => "null",
+            ValueKind::Boolean => "boolean",
+            ValueKind::Number => "number",
+            ValueKind::String => "string",
+            ValueKind::Array => "array",
+            ValueKind::Object => "object",
+        })
+    }
 }
 
 impl Value {
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Value::Null => ValueKind::Null,
+            Value::Boolean(_) => ValueKind::Boolean,
+            Value::Number(_) => ValueKind::Number,
+            Value::String(_) | Value::RawString(_) => ValueKind::String,
+            Value::Array(_) => ValueKind::Array,
+            Value::Object(_) => ValueKind::Object,
+        }
+    }
+
     pub fn as_string(&self) -> Result<&String, DecodeError> {
-        if let Value::String(s) = self {
-            Ok(s)
-        } else {
-            Err(DecodeError {})
+        match self {
+            Value::String(s) => Ok(s),
+            Value::RawString(_) => Err(DecodeError::custom("string contains unpaired surrogates")),
+            _ => Err(DecodeError::expected("string", self)),
         }
     }
 
-    pub fn as_float(&self) -> Result<f32, DecodeError> {
+    pub fn as_number(&self) -> Result<&Number, DecodeError> {
         if let Value::Number(n) = self {
-            Ok(*n)
+            Ok(n)
         } else {
-            Err(DecodeError {})
+            Err(DecodeError::expected("number", self))
+        }
+    }
+
+    pub fn as_float(&self) -> Result<f32, DecodeError> {
+        self.number_as("f32", Number::as_f32)
+    }
+
+    pub fn as_f64(&self) -> Result<f64, DecodeError> {
+        self.number_as("f64", Number::as_f64)
+    }
+
+    pub fn as_i64(&self) -> Result<i64, DecodeError> {
+        self.number_as("i64", Number::as_i64)
+    }
+
+    pub fn as_u64(&self) -> Result<u64, DecodeError> {
+        self.number_as("u64", Number::as_u64)
+    }
+
+    // Converts a number with f, which returns None if the conversion would lose information.
+    fn number_as<T, F>(&self, expected: &'static str, f: F) -> Result<T, DecodeError>
+    where
+        F: Fn(&Number) -> Option<T>,
+    {
+        match self {
+            Value::Number(n) => f(n).ok_or_else(|| DecodeError::inexact(expected)),
+            _ => Err(DecodeError::expected(expected, self)),
         }
     }
 
@@ -38,42 +163,131 @@ impl Value {
         if let Value::Array(a) = self {
             Ok(a)
         } else {
-            Err(DecodeError {})
+            Err(DecodeError::expected("array", self))
         }
     }
 
-    pub fn as_object(&self) -> Result<&HashMap<String, Value>, DecodeError> {
+    pub fn as_object(&self) -> Result<&Map, DecodeError> {
         if let Value::Object(hm) = self {
             Ok(hm)
         } else {
-            Err(DecodeError {})
+            Err(DecodeError::expected("object", self))
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, DecodeError> {
+        if let Value::Boolean(b) = self {
+            Ok(*b)
+        } else {
+            Err(DecodeError::expected("boolean", self))
+        }
+    }
+
+    pub fn as_bool_mut(&mut self) -> Result<&mut bool, DecodeError> {
+        match self {
+            Value::Boolean(b) => Ok(b),
+            _ => Err(DecodeError::expected("boolean", self)),
         }
     }
+
+    pub fn as_string_mut(&mut self) -> Result<&mut String, DecodeError> {
+        match self {
+            Value::String(s) => Ok(s),
+            Value::RawString(_) => Err(DecodeError::custom("string contains unpaired surrogates")),
+            _ => Err(DecodeError::expected("string", self)),
+        }
+    }
+
+    pub fn as_number_mut(&mut self) -> Result<&mut Number, DecodeError> {
+        match self {
+            Value::Number(n) => Ok(n),
+            _ => Err(DecodeError::expected("number", self)),
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Result<&mut Vec<Value>, DecodeError> {
+        match self {
+            Value::Array(a) => Ok(a),
+            _ => Err(DecodeError::expected("array", self)),
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Result<&mut Map, DecodeError> {
+        match self {
+            Value::Object(o) => Ok(o),
+            _ => Err(DecodeError::expected("object", self)),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn is_boolean(&self) -> bool {
+        matches!(self, Value::Boolean(_))
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Value::Number(_))
+    }
+
+    /// True for strings with unpaired surrogates too.
+    pub fn is_string(&self) -> bool {
+        matches!(self, Value::String(_) | Value::RawString(_))
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, Value::Array(_))
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self, Value::Object(_))
+    }
+
+    /// Moves the value out, leaving null in its place.
+    pub fn take(&mut self) -> Value {
+        std::mem::take(self)
+    }
+
+    /// Sets an object member, returning the old value if there was one.  Null becomes an empty
+    /// object first.  Any other kind of value is an error, and is left as it was.
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Result<Option<Value>, DecodeError>
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        if let Value::Null = self {
+            *self = Value::Object(Map::new());
+        }
+        Ok(self.as_object_mut()?.insert(key.into(), value.into()))
+    }
+
+    /// Adds an element to the end of an array.  Null becomes an empty array first.  Any other
+    /// kind of value is an error, and is left as it was.
+    pub fn push<V: Into<Value>>(&mut self, value: V) -> Result<(), DecodeError> {
+        if let Value::Null = self {
+            *self = Value::Array(Vec::new());
+        }
+        self.as_array_mut()?.push(value.into());
+        Ok(())
+    }
 }
 
-pub fn extract_field<T>(
-    o: &HashMap<String, Value>,
-    key: &str,
-    res: &mut T,
-) -> Result<(), DecodeError>
+pub fn extract_field<T>(o: &Map, key: &str, res: &mut T) -> Result<(), DecodeError>
 where
     T: FromJSON,
 {
     let v = match o.get(key) {
-        None => return Err(DecodeError {}),
+        None => return Err(DecodeError::missing_field(key)),
         Some(a) => a,
     };
 
-    T::from_json(v, res)?;
+    T::from_json(v, res).map_err(|e| e.at_key(key))?;
 
     Ok(())
 }
 
-pub fn extract_optional_field<T>(
-    o: &HashMap<String, Value>,
-    key: &str,
-    res: &mut Option<T>,
-) -> Result<(), DecodeError>
+pub fn extract_optional_field<T>(o: &Map, key: &str, res: &mut Option<T>) -> Result<(), DecodeError>
 where
     T: FromJSON + Default,
 {
@@ -84,15 +298,128 @@ where
 
     let mut r = Default::default();
 
-    T::from_json(v, &mut r)?;
+    T::from_json(v, &mut r).map_err(|e| e.at_key(key))?;
 
     *res = Some(r);
 
     Ok(())
 }
 
-#[derive(Debug, PartialEq)]
-pub struct DecodeError;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeError {
+    /// Where in the document the error happened, outermost segment first.
+    pub path: Vec<PathSegment>,
+    /// What the FromJSON impl was looking for, eg. "string" or "u32".
+    pub expected: Option<&'static str>,
+    /// What it found instead.
+    pub actual: Option<ValueKind>,
+    pub message: Option<String>,
+}
+
+impl DecodeError {
+    pub fn expected(expected: &'static str, actual: &Value) -> DecodeError {
+        DecodeError::expected_kind(expected, actual.kind())
+    }
+
+    pub(crate) fn expected_kind(expected: &'static str, actual: ValueKind) -> DecodeError {
+        DecodeError {
+            path: Vec::new(),
+            expected: Some(expected),
+            actual: Some(actual),
+            message: None,
+        }
+    }
+
+    // A number that cannot be converted to the expected type without losing information.
+    pub(crate) fn inexact(expected: &'static str) -> DecodeError {
+        DecodeError {
+            message: Some("number cannot be represented exactly".to_owned()),
+            ..DecodeError::expected_kind(expected, ValueKind::Number)
+        }
+    }
+
+    pub fn missing_field(key: &str) -> DecodeError {
+        DecodeError {
+            path: vec![PathSegment::Key(key.to_owned())],
+            expected: None,
+            actual: None,
+            message: Some("missing field".to_owned()),
+        }
+    }
+
+    pub fn custom<S: Into<String>>(message: S) -> DecodeError {
+        DecodeError {
+            path: Vec::new(),
+            expected: None,
+            actual: None,
+            message: Some(message.into()),
+        }
+    }
+
+    /// Records that the error happened inside the object member `key`.
+    pub fn at_key(mut self, key: &str) -> DecodeError {
+        self.path.insert(0, PathSegment::Key(key.to_owned()));
+        self
+    }
+
+    /// Records that the error happened inside the array element `index`.
+    pub fn at_index(mut self, index: usize) -> DecodeError {
+        self.path.insert(0, PathSegment::Index(index));
+        self
+    }
+
+    /// The path as a string, eg. `$.points[17].y`
+    pub fn path_string(&self) -> String {
+        let mut res = "$".to_owned();
+        for segment in &self.path {
+            match segment {
+                PathSegment::Key(k) => {
+                    let simple = !k.is_empty()
+                        && !k.starts_with(|c: char| c.is_ascii_digit())
+                        && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    if simple {
+                        res.push('.');
+                        res.push_str(k);
+                    } else {
+                        res.push_str(&format!("[{:?}]", k));
+                    }
+                }
+                PathSegment::Index(i) => res.push_str(&format!("[{}]", i)),
+            }
+        }
+        res
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.path_string())?;
+
+        if let Some(expected) = self.expected {
+            write!(f, "expected {}", expected)?;
+            if let Some(actual) = self.actual {
+                write!(f, ", got {}", actual)?;
+            }
+            if self.message.is_some() {
+                write!(f, ": ")?;
+            }
+        }
+
+        match &self.message {
+            Some(m) => f.write_str(m),
+            None if self.expected.is_none() => f.write_str("invalid value"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
 
 pub trait FromJSON {
     // fn from_json(v: &Value) -> Result<Self, DecodeError>;
@@ -107,6 +434,13 @@ impl FromJSON for String {
     }
 }
 
+impl FromJSON for bool {
+    fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.as_bool()?;
+        Ok(())
+    }
+}
+
 impl FromJSON for f32 {
     fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
         let n = v.as_float()?;
@@ -115,15 +449,38 @@ impl FromJSON for f32 {
     }
 }
 
+impl FromJSON for f64 {
+    fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.as_f64()?;
+        Ok(())
+    }
+}
+
 impl FromJSON for u32 {
     fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
-        let n = v.as_float()?;
-        if n != n.floor() {
-            Err(DecodeError {})
-        } else {
-            *res = n as u32;
-            Ok(())
-        }
+        *res = v.number_as("u32", |n| n.as_u64().and_then(|n| u32::try_from(n).ok()))?;
+        Ok(())
+    }
+}
+
+impl FromJSON for u64 {
+    fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.as_u64()?;
+        Ok(())
+    }
+}
+
+impl FromJSON for i32 {
+    fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.number_as("i32", |n| n.as_i64().and_then(|n| i32::try_from(n).ok()))?;
+        Ok(())
+    }
+}
+
+impl FromJSON for i64 {
+    fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.as_i64()?;
+        Ok(())
     }
 }
 
@@ -136,9 +493,9 @@ where
         res.clear();
         res.reserve_exact(a.len());
 
-        for elem in a {
+        for (i, elem) in a.iter().enumerate() {
             let mut e = Default::default();
-            FromJSON::from_json(elem, &mut e)?;
+            FromJSON::from_json(elem, &mut e).map_err(|e| e.at_index(i))?;
             res.push(e);
         }
 
@@ -155,9 +512,9 @@ where
         res.clear();
         res.reserve(a.len());
 
-        for elem in a {
+        for (i, elem) in a.iter().enumerate() {
             let mut e = Default::default();
-            FromJSON::from_json(elem, &mut e)?;
+            FromJSON::from_json(elem, &mut e).map_err(|e| e.at_index(i))?;
             res.insert(e);
         }
 
@@ -165,29 +522,64 @@ where
     }
 }
 
+// Decodes each member of an object, parsing keys with FromStr.
+fn decode_members<K, V, F>(v: &Value, mut insert: F) -> Result<(), DecodeError>
+where
+    K: FromStr,
+    V: FromJSON + Default,
+    F: FnMut(K, V),
+{
+    for (k, v) in v.as_object()? {
+        let key = match FromStr::from_str(k.as_str()) {
+            Ok(k) => k,
+            Err(_) => return Err(DecodeError::custom("invalid key").at_key(k)),
+        };
+
+        let mut value = Default::default();
+        FromJSON::from_json(v, &mut value).map_err(|e| e.at_key(k))?;
+
+        insert(key, value);
+    }
+
+    Ok(())
+}
+
 impl<K, V> FromJSON for HashMap<K, V>
 where
     K: FromJSON + FromStr + Eq + Hash,
     V: FromJSON + Default,
 {
     fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
-        let hm = v.as_object()?;
-
         res.clear();
+        decode_members(v, |k, v| {
+            res.insert(k, v);
+        })
+    }
+}
 
-        for (k, v) in hm {
-            let key = match FromStr::from_str(k.as_str()) {
-                Ok(k) => k,
-                Err(_) => return Err(DecodeError {}), // FIXME
-            };
-
-            let mut value = Default::default();
-            FromJSON::from_json(v, &mut value)?;
-
-            res.insert(key, value);
-        }
+impl<K, V> FromJSON for BTreeMap<K, V>
+where
+    K: FromStr + Ord,
+    V: FromJSON + Default,
+{
+    fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
+        res.clear();
+        decode_members(v, |k, v| {
+            res.insert(k, v);
+        })
+    }
+}
 
-        Ok(())
+impl<K, V> FromJSON for Map<K, V>
+where
+    K: FromStr + Eq + Hash,
+    V: FromJSON + Default,
+{
+    fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
+        res.clear();
+        decode_members(v, |k, v| {
+            res.insert(k, v);
+        })
     }
 }
 
@@ -207,32 +599,300 @@ where
     }
 }
 
+pub trait ToJSON {
+    fn to_json(&self) -> Value;
+}
+
+impl ToJSON for Value {
+    fn to_json(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl ToJSON for String {
+    fn to_json(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl ToJSON for str {
+    fn to_json(&self) -> Value {
+        Value::String(self.to_owned())
+    }
+}
+
+impl ToJSON for bool {
+    fn to_json(&self) -> Value {
+        Value::Boolean(*self)
+    }
+}
+
+impl ToJSON for f32 {
+    fn to_json(&self) -> Value {
+        Value::Number(Number::from(*self))
+    }
+}
+
+impl ToJSON for f64 {
+    fn to_json(&self) -> Value {
+        Value::Number(Number::from(*self))
+    }
+}
+
+impl ToJSON for u32 {
+    fn to_json(&self) -> Value {
+        Value::Number(Number::from(*self))
+    }
+}
+
+impl ToJSON for u64 {
+    fn to_json(&self) -> Value {
+        Value::Number(Number::from(*self))
+    }
+}
+
+impl ToJSON for i32 {
+    fn to_json(&self) -> Value {
+        Value::Number(Number::from(*self))
+    }
+}
+
+impl ToJSON for i64 {
+    fn to_json(&self) -> Value {
+        Value::Number(Number::from(*self))
+    }
+}
+
+impl<T> ToJSON for Vec<T>
+where
+    T: ToJSON,
+{
+    fn to_json(&self) -> Value {
+        Value::Array(self.iter().map(ToJSON::to_json).collect())
+    }
+}
+
+impl<T> ToJSON for std::collections::HashSet<T>
+where
+    T: ToJSON,
+{
+    fn to_json(&self) -> Value {
+        Value::Array(self.iter().map(ToJSON::to_json).collect())
+    }
+}
+
+impl<K, V> ToJSON for HashMap<K, V>
+where
+    K: ToString,
+    V: ToJSON,
+{
+    fn to_json(&self) -> Value {
+        Value::Object(
+            self.iter()
+                .map(|(k, v)| (k.to_string(), v.to_json()))
+                .collect(),
+        )
+    }
+}
+
+impl<K, V> ToJSON for BTreeMap<K, V>
+where
+    K: ToString,
+    V: ToJSON,
+{
+    fn to_json(&self) -> Value {
+        Value::Object(
+            self.iter()
+                .map(|(k, v)| (k.to_string(), v.to_json()))
+                .collect(),
+        )
+    }
+}
+
+impl<K, V> ToJSON for Map<K, V>
+where
+    K: ToString,
+    V: ToJSON,
+{
+    fn to_json(&self) -> Value {
+        Value::Object(
+            self.iter()
+                .map(|(k, v)| (k.to_string(), v.to_json()))
+                .collect(),
+        )
+    }
+}
+
+impl<T> ToJSON for Option<T>
+where
+    T: ToJSON,
+{
+    fn to_json(&self) -> Value {
+        match self {
+            None => Value::Null,
+            Some(v) => v.to_json(),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Boolean(b)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Value {
+        Value::Number(Number::from(n))
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Value {
+        Value::Number(Number::from(n))
+    }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Value {
+        Value::Number(Number::from(n))
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Value {
+        Value::Number(Number::from(n))
+    }
+}
+
+impl From<f32> for Value {
+    fn from(n: f32) -> Value {
+        Value::Number(Number::from(n))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(Number::from(n))
+    }
+}
+
+impl From<Number> for Value {
+    fn from(n: Number) -> Value {
+        Value::Number(n)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_owned())
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(a: Vec<T>) -> Value {
+        Value::Array(a.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Map> for Value {
+    fn from(o: Map) -> Value {
+        Value::Object(o)
+    }
+}
+
+/// None becomes null.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(o: Option<T>) -> Value {
+        match o {
+            Some(v) => v.into(),
+            None => Value::Null,
+        }
+    }
+}
+
+/// Collects into an array.
+impl<T: Into<Value>> FromIterator<T> for Value {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Value {
+        Value::Array(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Collects key-value pairs into an object.
+impl<K: Into<String>, V: Into<Value>> FromIterator<(K, V)> for Value {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Value {
+        Value::Object(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum JSONError {
-    ParseError(String),
-    DecodeError,
+    ParseError(ParseError),
+    DecodeError(DecodeError),
+}
+
+impl fmt::Display for JSONError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JSONError::ParseError(p) => p.fmt(f),
+            JSONError::DecodeError(d) => d.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for JSONError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JSONError::ParseError(p) => Some(p),
+            JSONError::DecodeError(d) => Some(d),
+        }
+    }
 }
 
 impl From<ParseError> for JSONError {
     fn from(p: ParseError) -> JSONError {
-        let ParseError(msg) = p;
-        JSONError::ParseError(msg)
+        JSONError::ParseError(p)
     }
 }
 
 impl From<DecodeError> for JSONError {
-    fn from(_: DecodeError) -> JSONError {
-        JSONError::DecodeError {}
+    fn from(d: DecodeError) -> JSONError {
+        JSONError::DecodeError(d)
     }
 }
 
+/// Parses and decodes a document.  Like parse, this never panics on bad input; it returns an error.
 pub fn decode<T>(s: &str) -> Result<T, JSONError>
 where
     T: FromJSON + Default,
 {
-    let v = parse(s)?;
+    decode_with(s, &Default::default())
+}
+
+pub fn decode_with<T>(s: &str, options: &ParseOptions) -> Result<T, JSONError>
+where
+    T: FromJSON + Default,
+{
+    let v = parse_with(s, options)?;
 
     let mut res: T = Default::default();
     FromJSON::from_json(&v, &mut res)?;
     Ok(res)
 }
+
+pub fn encode<T>(v: &T) -> String
+where
+    T: ToJSON + ?Sized,
+{
+    to_string(&v.to_json())
+}
@@ -0,0 +1,464 @@
+use std::borrow::Cow;
+use std::collections::hash_map::HashMap;
+use std::collections::{BTreeMap, HashSet};
+use std::hash::Hash;
+use std::str::FromStr;
+
+use crate::fortunate_json::map;
+use crate::fortunate_json::parse::{lossy_wtf8, parse_tree, Tree};
+use crate::fortunate_json::reader::Event;
+use crate::fortunate_json::{
+    DecodeError, JSONError, Map, Number, ParseError, ParseOptions, Value, ValueKind,
+};
+
+/// A JSON document whose strings point into the text it was parsed from, unless they had to be
+/// unescaped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BorrowedValue<'a> {
+    Null,
+    Boolean(bool),
+    Number(Number),
+    String(Cow<'a, str>),
+    Array(Vec<BorrowedValue<'a>>),
+    Object(Map<Cow<'a, str>, BorrowedValue<'a>>),
+}
+
+// Like Value's, so that deep documents don't overflow the stack when they are dropped.
+impl<'a> Drop for BorrowedValue<'a> {
+    fn drop(&mut self) {
+        let mut pending = match self {
+            BorrowedValue::Array(a) if !a.is_empty() => std::mem::take(a),
+            BorrowedValue::Object(o) if !o.is_empty() => {
+                std::mem::take(o).into_iter().map(|(_, v)| v).collect()
+            }
+            _ => return,
+        };
+
+        while let Some(mut v) = pending.pop() {
+            match &mut v {
+                BorrowedValue::Array(a) => pending.append(a),
+                BorrowedValue::Object(o) => {
+                    pending.extend(std::mem::take(o).into_iter().map(|(_, v)| v))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// An array or object that into_owned is partway through.
+enum Converting<'a> {
+    Array(std::vec::IntoIter<BorrowedValue<'a>>, Vec<Value>),
+    // Along with the key of the member being converted.
+    Object(map::IntoIter<Cow<'a, str>, BorrowedValue<'a>>, Map, String),
+}
+
+impl<'a> BorrowedValue<'a> {
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            BorrowedValue::Null => ValueKind::Null,
+            BorrowedValue::Boolean(_) => ValueKind::Boolean,
+            BorrowedValue::Number(_) => ValueKind::Number,
+            BorrowedValue::String(_) => ValueKind::String,
+            BorrowedValue::Array(_) => ValueKind::Array,
+            BorrowedValue::Object(_) => ValueKind::Object,
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, DecodeError> {
+        if let BorrowedValue::Boolean(b) = self {
+            Ok(*b)
+        } else {
+            Err(DecodeError::expected_kind("boolean", self.kind()))
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&Cow<'a, str>, DecodeError> {
+        if let BorrowedValue::String(s) = self {
+            Ok(s)
+        } else {
+            Err(DecodeError::expected_kind("string", self.kind()))
+        }
+    }
+
+    pub fn as_array(&self) -> Result<&Vec<BorrowedValue<'a>>, DecodeError> {
+        if let BorrowedValue::Array(a) = self {
+            Ok(a)
+        } else {
+            Err(DecodeError::expected_kind("array", self.kind()))
+        }
+    }
+
+    pub fn as_object(&self) -> Result<&Map<Cow<'a, str>, BorrowedValue<'a>>, DecodeError> {
+        if let BorrowedValue::Object(o) = self {
+            Ok(o)
+        } else {
+            Err(DecodeError::expected_kind("object", self.kind()))
+        }
+    }
+
+    fn number_as<T, F>(&self, expected: &'static str, f: F) -> Result<T, DecodeError>
+    where
+        F: Fn(&Number) -> Option<T>,
+    {
+        match self {
+            BorrowedValue::Number(n) => f(n).ok_or_else(|| DecodeError::inexact(expected)),
+            _ => Err(DecodeError::expected_kind(expected, self.kind())),
+        }
+    }
+
+    /// Copies the strings that are borrowed.  Works without recursion, however deep the document.
+    pub fn into_owned(self) -> Value {
+        let mut stack: Vec<Converting<'a>> = Vec::new();
+        let mut next = self;
+
+        loop {
+            // BorrowedValue implements Drop, so its contents have to be taken rather than moved.
+            let mut done = match &mut next {
+                BorrowedValue::Null => Some(Value::Null),
+                BorrowedValue::Boolean(b) => Some(Value::Boolean(*b)),
+                BorrowedValue::Number(n) => Some(Value::Number(n.clone())),
+                BorrowedValue::String(s) => Some(Value::String(std::mem::take(s).into_owned())),
+                BorrowedValue::Array(a) => {
+                    let len = a.len();
+                    let items = std::mem::take(a).into_iter();
+                    stack.push(Converting::Array(items, Vec::with_capacity(len)));
+                    None
+                }
+                BorrowedValue::Object(o) => {
+                    let len = o.len();
+                    let members = std::mem::take(o).into_iter();
+                    stack.push(Converting::Object(
+                        members,
+                        Map::with_capacity(len),
+                        String::new(),
+                    ));
+                    None
+                }
+            };
+
+            // Adds what was just converted to its container, and finishes the containers that it
+            // completes, until there is another value to convert.
+            loop {
+                let child = match stack.last_mut() {
+                    None => return done.unwrap_or_default(),
+                    Some(Converting::Array(items, res)) => {
+                        res.extend(done.take());
+                        items.next()
+                    }
+                    Some(Converting::Object(members, res, key)) => {
+                        if let Some(v) = done.take() {
+                            res.insert(std::mem::take(key), v);
+                        }
+                        members.next().map(|(k, v)| {
+                            *key = k.into_owned();
+                            v
+                        })
+                    }
+                };
+
+                match child {
+                    Some(child) => {
+                        next = child;
+                        break;
+                    }
+                    None => {
+                        done = match stack.pop() {
+                            Some(Converting::Array(_, res)) => Some(Value::Array(res)),
+                            Some(Converting::Object(_, res, _)) => Some(Value::Object(res)),
+                            None => None,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Tree<'a> for BorrowedValue<'a> {
+    type Key = Cow<'a, str>;
+
+    fn key(k: Cow<'a, str>) -> Cow<'a, str> {
+        k
+    }
+
+    fn scalar(event: Event<'a>) -> BorrowedValue<'a> {
+        match event {
+            Event::String(s) => BorrowedValue::String(s),
+            Event::Number(n) => BorrowedValue::Number(n),
+            Event::Boolean(b) => BorrowedValue::Boolean(b),
+            _ => BorrowedValue::Null,
+        }
+    }
+
+    // There is no BorrowedValue for strings with lone surrogates, so they are replaced.
+    fn raw_string(bytes: Vec<u8>) -> BorrowedValue<'a> {
+        BorrowedValue::String(Cow::Owned(lossy_wtf8(&bytes)))
+    }
+
+    fn array(a: Vec<BorrowedValue<'a>>) -> BorrowedValue<'a> {
+        BorrowedValue::Array(a)
+    }
+
+    fn object(o: Map<Cow<'a, str>, BorrowedValue<'a>>) -> BorrowedValue<'a> {
+        BorrowedValue::Object(o)
+    }
+
+    fn array_mut(&mut self) -> Option<&mut Vec<BorrowedValue<'a>>> {
+        match self {
+            BorrowedValue::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a document with the default options, borrowing strings from it where possible.
+pub fn parse_borrowed(s: &str) -> Result<BorrowedValue<'_>, ParseError> {
+    parse_borrowed_with(s, &Default::default())
+}
+
+/// Like parse_with, with the same limits and policies.  LoneSurrogates::Preserve acts like
+/// LoneSurrogates::Replace, since a BorrowedValue can only hold valid strings.
+pub fn parse_borrowed_with<'a>(
+    s: &'a str,
+    options: &ParseOptions,
+) -> Result<BorrowedValue<'a>, ParseError> {
+    parse_tree(s.as_bytes(), options)
+}
+
+/// Like FromJSON, but can borrow strings from the text the document was parsed from.
+pub trait FromBorrowedJSON<'a> {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError>;
+}
+
+pub fn extract_borrowed_field<'a, T>(
+    o: &Map<Cow<'a, str>, BorrowedValue<'a>>,
+    key: &str,
+    res: &mut T,
+) -> Result<(), DecodeError>
+where
+    T: FromBorrowedJSON<'a>,
+{
+    let v = match o.get(key) {
+        None => return Err(DecodeError::missing_field(key)),
+        Some(a) => a,
+    };
+
+    T::from_borrowed_json(v, res).map_err(|e| e.at_key(key))
+}
+
+impl<'a> FromBorrowedJSON<'a> for &'a str {
+    // Fails if the string had escapes in it, because then there is nothing in the input to point
+    // at.  Decode into a Cow<'a, str> to accept those as well.
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        match v.as_str()? {
+            Cow::Borrowed(s) => {
+                *res = s;
+                Ok(())
+            }
+            Cow::Owned(_) => Err(DecodeError::custom(
+                "string contains escape sequences and cannot be borrowed",
+            )),
+        }
+    }
+}
+
+impl<'a> FromBorrowedJSON<'a> for Cow<'a, str> {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        res.clone_from(v.as_str()?);
+        Ok(())
+    }
+}
+
+impl<'a> FromBorrowedJSON<'a> for String {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        res.clear();
+        res.push_str(v.as_str()?);
+        Ok(())
+    }
+}
+
+impl<'a> FromBorrowedJSON<'a> for bool {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.as_bool()?;
+        Ok(())
+    }
+}
+
+impl<'a> FromBorrowedJSON<'a> for f32 {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.number_as("f32", Number::as_f32)?;
+        Ok(())
+    }
+}
+
+impl<'a> FromBorrowedJSON<'a> for f64 {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.number_as("f64", Number::as_f64)?;
+        Ok(())
+    }
+}
+
+impl<'a> FromBorrowedJSON<'a> for u32 {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.number_as("u32", |n| n.as_u64().and_then(|n| u32::try_from(n).ok()))?;
+        Ok(())
+    }
+}
+
+impl<'a> FromBorrowedJSON<'a> for u64 {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.number_as("u64", Number::as_u64)?;
+        Ok(())
+    }
+}
+
+impl<'a> FromBorrowedJSON<'a> for i32 {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.number_as("i32", |n| n.as_i64().and_then(|n| i32::try_from(n).ok()))?;
+        Ok(())
+    }
+}
+
+impl<'a> FromBorrowedJSON<'a> for i64 {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        *res = v.number_as("i64", Number::as_i64)?;
+        Ok(())
+    }
+}
+
+impl<'a, T> FromBorrowedJSON<'a> for Vec<T>
+where
+    T: FromBorrowedJSON<'a> + Default,
+{
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        let a = v.as_array()?;
+        res.clear();
+        res.reserve_exact(a.len());
+
+        for (i, elem) in a.iter().enumerate() {
+            let mut e = Default::default();
+            T::from_borrowed_json(elem, &mut e).map_err(|e| e.at_index(i))?;
+            res.push(e);
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, T> FromBorrowedJSON<'a> for HashSet<T>
+where
+    T: FromBorrowedJSON<'a> + Default + Eq + Hash,
+{
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        let a = v.as_array()?;
+        res.clear();
+        res.reserve(a.len());
+
+        for (i, elem) in a.iter().enumerate() {
+            let mut e = Default::default();
+            T::from_borrowed_json(elem, &mut e).map_err(|e| e.at_index(i))?;
+            res.insert(e);
+        }
+
+        Ok(())
+    }
+}
+
+// Like decode_members, for a BorrowedValue.
+fn decode_borrowed_members<'a, K, V, F>(
+    v: &BorrowedValue<'a>,
+    mut insert: F,
+) -> Result<(), DecodeError>
+where
+    K: FromStr,
+    V: FromBorrowedJSON<'a> + Default,
+    F: FnMut(K, V),
+{
+    for (k, v) in v.as_object()? {
+        let key = match FromStr::from_str(k) {
+            Ok(k) => k,
+            Err(_) => return Err(DecodeError::custom("invalid key").at_key(k)),
+        };
+
+        let mut value = Default::default();
+        V::from_borrowed_json(v, &mut value).map_err(|e| e.at_key(k))?;
+
+        insert(key, value);
+    }
+
+    Ok(())
+}
+
+impl<'a, K, V> FromBorrowedJSON<'a> for HashMap<K, V>
+where
+    K: FromStr + Eq + Hash,
+    V: FromBorrowedJSON<'a> + Default,
+{
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        res.clear();
+        decode_borrowed_members(v, |k, v| {
+            res.insert(k, v);
+        })
+    }
+}
+
+impl<'a, K, V> FromBorrowedJSON<'a> for BTreeMap<K, V>
+where
+    K: FromStr + Ord,
+    V: FromBorrowedJSON<'a> + Default,
+{
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        res.clear();
+        decode_borrowed_members(v, |k, v| {
+            res.insert(k, v);
+        })
+    }
+}
+
+impl<'a, K, V> FromBorrowedJSON<'a> for Map<K, V>
+where
+    K: FromStr + Eq + Hash,
+    V: FromBorrowedJSON<'a> + Default,
+{
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        res.clear();
+        decode_borrowed_members(v, |k, v| {
+            res.insert(k, v);
+        })
+    }
+}
+
+impl<'a, T> FromBorrowedJSON<'a> for Option<T>
+where
+    T: FromBorrowedJSON<'a> + Default,
+{
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        if let BorrowedValue::Null = v {
+            *res = None;
+        } else {
+            let mut r = Default::default();
+            T::from_borrowed_json(v, &mut r)?;
+            *res = Some(r);
+        }
+        Ok(())
+    }
+}
+
+pub fn decode_borrowed<'a, T>(s: &'a str) -> Result<T, JSONError>
+where
+    T: FromBorrowedJSON<'a> + Default,
+{
+    decode_borrowed_with(s, &Default::default())
+}
+
+pub fn decode_borrowed_with<'a, T>(s: &'a str, options: &ParseOptions) -> Result<T, JSONError>
+where
+    T: FromBorrowedJSON<'a> + Default,
+{
+    let v = parse_borrowed_with(s, options)?;
+
+    let mut res: T = Default::default();
+    T::from_borrowed_json(&v, &mut res)?;
+    Ok(res)
+}
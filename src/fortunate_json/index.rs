@@ -0,0 +1,156 @@
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use crate::fortunate_json::{Map, Value};
+
+/// Something a Value can be indexed by: a `usize` for an array element, or a string for an
+/// object member.
+pub trait ValueIndex: fmt::Debug {
+    fn get_in<'v>(&self, v: &'v Value) -> Option<&'v Value>;
+    fn get_in_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value>;
+    fn remove_from(&self, v: &mut Value) -> Option<Value>;
+
+    /// What `v[index] = ...` assigns to.  By default the same as get_in_mut.
+    fn index_or_insert<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        self.get_in_mut(v)
+    }
+}
+
+impl ValueIndex for usize {
+    fn get_in<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        match v {
+            Value::Array(a) => a.get(*self),
+            _ => None,
+        }
+    }
+
+    fn get_in_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        match v {
+            Value::Array(a) => a.get_mut(*self),
+            _ => None,
+        }
+    }
+
+    fn remove_from(&self, v: &mut Value) -> Option<Value> {
+        match v {
+            Value::Array(a) if *self < a.len() => Some(a.remove(*self)),
+            _ => None,
+        }
+    }
+}
+
+impl ValueIndex for str {
+    fn get_in<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        match v {
+            Value::Object(o) => o.get(self),
+            _ => None,
+        }
+    }
+
+    fn get_in_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        match v {
+            Value::Object(o) => o.get_mut(self),
+            _ => None,
+        }
+    }
+
+    fn remove_from(&self, v: &mut Value) -> Option<Value> {
+        match v {
+            Value::Object(o) => o.remove(self),
+            _ => None,
+        }
+    }
+
+    // Like Value::insert, a missing member is added as null, and null becomes an empty object.
+    fn index_or_insert<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        if let Value::Null = v {
+            *v = Value::Object(Map::new());
+        }
+        match v {
+            Value::Object(o) => {
+                if !o.contains_key(self) {
+                    o.insert(self.to_owned(), Value::Null);
+                }
+                o.get_mut(self)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ValueIndex for String {
+    fn get_in<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        self.as_str().get_in(v)
+    }
+
+    fn get_in_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        self.as_str().get_in_mut(v)
+    }
+
+    fn remove_from(&self, v: &mut Value) -> Option<Value> {
+        self.as_str().remove_from(v)
+    }
+
+    fn index_or_insert<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        self.as_str().index_or_insert(v)
+    }
+}
+
+impl<T: ValueIndex + ?Sized> ValueIndex for &T {
+    fn get_in<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        (**self).get_in(v)
+    }
+
+    fn get_in_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        (**self).get_in_mut(v)
+    }
+
+    fn remove_from(&self, v: &mut Value) -> Option<Value> {
+        (**self).remove_from(v)
+    }
+
+    fn index_or_insert<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        (**self).index_or_insert(v)
+    }
+}
+
+impl Value {
+    /// The element of an array or member of an object.  None if there isn't one, or this is
+    /// some other kind of value.
+    pub fn get<I: ValueIndex>(&self, index: I) -> Option<&Value> {
+        index.get_in(self)
+    }
+
+    pub fn get_mut<I: ValueIndex>(&mut self, index: I) -> Option<&mut Value> {
+        index.get_in_mut(self)
+    }
+
+    /// Removes an element from an array, moving the later ones down, or a member from an object,
+    /// keeping the order of the rest.
+    pub fn remove<I: ValueIndex>(&mut self, index: I) -> Option<Value> {
+        index.remove_from(self)
+    }
+}
+
+// Like Map, indexing panics if the element or member isn't there.  Use get to find out.  Assigning
+// to a member that isn't there adds it, though, so `v["a"]["b"] = x` builds objects as it goes.
+impl<I: ValueIndex> Index<I> for Value {
+    type Output = Value;
+
+    fn index(&self, index: I) -> &Value {
+        match index.get_in(self) {
+            Some(v) => v,
+            None => panic!("{:?} not present in {}", index, self.kind()),
+        }
+    }
+}
+
+impl<I: ValueIndex> IndexMut<I> for Value {
+    fn index_mut(&mut self, index: I) -> &mut Value {
+        let kind = self.kind();
+        match index.index_or_insert(self) {
+            Some(v) => v,
+            None => panic!("{:?} not present in {}", index, kind),
+        }
+    }
+}
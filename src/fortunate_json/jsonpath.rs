@@ -0,0 +1,1062 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::fortunate_json::{JsonPointer, Number, PathSegment, Value};
+
+mod iregexp;
+
+use iregexp::Regex;
+
+// Integers in selectors must be exactly representable as an f64.
+const MAX_INDEX: i64 = (1 << 53) - 1;
+// How many parentheses, filters and function calls may be nested inside each other.
+const MAX_NESTING: usize = 64;
+
+/// A compiled JSONPath query (RFC 9535), like `$.points[?@.x > 5].y` or `$..name`.
+///
+/// Parse it once and run it against as many documents as needed.  The functions length, count,
+/// match, search and value are supported.  Regular expressions for match and search are
+/// I-Regexps (RFC 9485).  Category escapes like `\p{Lu}` go by the general categories of Unicode
+/// 14.0.  A pattern that isn't a valid I-Regexp matches nothing.
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+    source: String,
+    query: Query,
+}
+
+/// Why a JSONPath query couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPathError {
+    /// In bytes, from the start of the query.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for JsonPathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for JsonPathError {}
+
+/// A value selected by a query, and where it is in the document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node<'v> {
+    pub path: Vec<PathSegment>,
+    pub value: &'v Value,
+}
+
+impl<'v> Node<'v> {
+    /// The path in RFC 9535's normalized form, eg. `$['points'][3]['y']`.
+    pub fn normalized_path(&self) -> String {
+        let mut s = "$".to_owned();
+        for segment in &self.path {
+            match segment {
+                PathSegment::Key(k) => {
+                    s.push_str("['");
+                    for c in k.chars() {
+                        match c {
+                            '\u{8}' => s.push_str("\\b"),
+                            '\u{c}' => s.push_str("\\f"),
+                            '\n' => s.push_str("\\n"),
+                            '\r' => s.push_str("\\r"),
+                            '\t' => s.push_str("\\t"),
+                            '\'' => s.push_str("\\'"),
+                            '\\' => s.push_str("\\\\"),
+                            c if c < ' ' => s.push_str(&format!("\\u{:04x}", c as u32)),
+                            c => s.push(c),
+                        }
+                    }
+                    s.push_str("']");
+                }
+                PathSegment::Index(i) => s.push_str(&format!("[{}]", i)),
+            }
+        }
+        s
+    }
+
+    pub fn pointer(&self) -> JsonPointer {
+        let mut pointer = JsonPointer::root();
+        for segment in &self.path {
+            match segment {
+                PathSegment::Key(k) => pointer.push(k.as_str()),
+                PathSegment::Index(i) => pointer.push(i.to_string()),
+            }
+        }
+        pointer
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Query {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+    // A descendant segment (..) applies its selectors to the node and everything under it.
+    descendant: bool,
+    selectors: Vec<Selector>,
+}
+
+#[derive(Debug, Clone)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+struct FilterQuery {
+    // Starts at @ rather than $.
+    relative: bool,
+    query: Query,
+}
+
+impl FilterQuery {
+    // Whether the query can select at most one node.
+    fn is_singular(&self) -> bool {
+        self.query.segments.iter().all(|s| {
+            !s.descendant
+                && matches!(
+                    s.selectors.as_slice(),
+                    [Selector::Name(_) | Selector::Index(_)]
+                )
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Vec<Expr>),
+    And(Vec<Expr>),
+    Not(Box<Expr>),
+    // True if the query selects anything.
+    Exists(FilterQuery),
+    // A function that returns true or false.
+    Test(Function),
+    Compare(Comparable, CompareOp, Comparable),
+}
+
+// Something that evaluates to a single value, or to nothing.
+#[derive(Debug, Clone)]
+enum Comparable {
+    Literal(Value),
+    Query(FilterQuery),
+    Function(Function),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Function {
+    Length(Box<Comparable>),
+    Count(FilterQuery),
+    Match(Box<Comparable>, Box<Pattern>),
+    Search(Box<Comparable>, Box<Pattern>),
+    Value(FilterQuery),
+}
+
+impl Function {
+    // Whether it returns a value, as opposed to true or false.
+    fn returns_value(&self) -> bool {
+        matches!(
+            self,
+            Function::Length(_) | Function::Count(_) | Function::Value(_)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    // Written as a string literal, so compiled along with the query.  None if it's invalid.
+    Compiled(Option<Regex>),
+    Dynamic(Comparable),
+}
+
+// What an operand in a filter turned out to be, before checking that it's allowed there.
+enum Operand {
+    Literal(Value),
+    Query(FilterQuery),
+    Function(Function),
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+    nesting: usize,
+}
+
+fn is_blank(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r')
+}
+
+fn is_name_first(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+impl<'a> Parser<'a> {
+    fn error<S: Into<String>>(&self, message: S) -> JsonPathError {
+        JsonPathError {
+            offset: self.pos,
+            message: message.into(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), JsonPathError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", c)))
+        }
+    }
+
+    fn skip_blank(&mut self) {
+        while self.peek().is_some_and(is_blank) {
+            self.pos += 1;
+        }
+    }
+
+    fn nest(&mut self) -> Result<(), JsonPathError> {
+        self.nesting += 1;
+        if self.nesting > MAX_NESTING {
+            return Err(self.error("query is nested too deeply"));
+        }
+        Ok(())
+    }
+
+    // The segments after $ or @.
+    fn query(&mut self) -> Result<Query, JsonPathError> {
+        let mut segments = Vec::new();
+        loop {
+            // Blanks are allowed before a segment, but not after the last one.
+            let before = self.pos;
+            self.skip_blank();
+
+            if self.eat_str("..") {
+                let selectors = match self.peek() {
+                    Some('[') => self.bracketed()?,
+                    Some('*') => {
+                        self.pos += 1;
+                        vec![Selector::Wildcard]
+                    }
+                    _ => vec![Selector::Name(self.member_name()?)],
+                };
+                segments.push(Segment {
+                    descendant: true,
+                    selectors,
+                });
+            } else if self.eat('.') {
+                let selector = if self.eat('*') {
+                    Selector::Wildcard
+                } else {
+                    Selector::Name(self.member_name()?)
+                };
+                segments.push(Segment {
+                    descendant: false,
+                    selectors: vec![selector],
+                });
+            } else if self.peek() == Some('[') {
+                segments.push(Segment {
+                    descendant: false,
+                    selectors: self.bracketed()?,
+                });
+            } else {
+                self.pos = before;
+                return Ok(Query { segments });
+            }
+        }
+    }
+
+    fn member_name(&mut self) -> Result<String, JsonPathError> {
+        if !self.peek().is_some_and(is_name_first) {
+            return Err(self.error("expected a member name"));
+        }
+        let len = self
+            .rest()
+            .find(|c: char| !is_name_first(c) && !c.is_ascii_digit())
+            .unwrap_or(self.rest().len());
+        let name = self.rest()[..len].to_owned();
+        self.pos += len;
+        Ok(name)
+    }
+
+    fn bracketed(&mut self) -> Result<Vec<Selector>, JsonPathError> {
+        self.expect('[')?;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_blank();
+            selectors.push(self.selector()?);
+            self.skip_blank();
+            if self.eat(']') {
+                return Ok(selectors);
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, JsonPathError> {
+        match self.peek() {
+            Some(quote @ ('\'' | '"')) => {
+                self.pos += 1;
+                Ok(Selector::Name(self.string(quote)?))
+            }
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.pos += 1;
+                self.skip_blank();
+                self.nest()?;
+                let expr = self.logical_or()?;
+                self.nesting -= 1;
+                Ok(Selector::Filter(Box::new(expr)))
+            }
+            _ => {
+                let start = self.optional_int()?;
+                self.skip_blank();
+                if !self.eat(':') {
+                    return match start {
+                        Some(i) => Ok(Selector::Index(i)),
+                        None => Err(self.error("expected a selector")),
+                    };
+                }
+                self.skip_blank();
+                let end = self.optional_int()?;
+                self.skip_blank();
+                let step = if self.eat(':') {
+                    self.skip_blank();
+                    self.optional_int()?
+                } else {
+                    None
+                };
+                Ok(Selector::Slice(start, end, step))
+            }
+        }
+    }
+
+    fn optional_int(&mut self) -> Result<Option<i64>, JsonPathError> {
+        if !matches!(self.peek(), Some('-' | '0'..='9')) {
+            return Ok(None);
+        }
+
+        let start = self.pos;
+        let negative = self.eat('-');
+        let digits = self.rest().bytes().take_while(u8::is_ascii_digit).count();
+        let text = &self.rest()[..digits];
+        if digits == 0 || (text.starts_with('0') && (digits > 1 || negative)) {
+            self.pos = start;
+            return Err(self.error("invalid integer"));
+        }
+        self.pos += digits;
+
+        match text.parse::<i64>() {
+            Ok(n) if n <= MAX_INDEX => Ok(Some(if negative { -n } else { n })),
+            _ => {
+                self.pos = start;
+                Err(self.error("integer out of range"))
+            }
+        }
+    }
+
+    // After the opening quote.
+    fn string(&mut self, quote: char) -> Result<String, JsonPathError> {
+        let mut s = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            if c < ' ' {
+                return Err(self.error("control character in string"));
+            }
+            self.pos += c.len_utf8();
+            match c {
+                c if c == quote => return Ok(s),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some(c @ ('/' | '\\')) => c,
+                        Some(c) if c == quote => c,
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            self.pos += 1;
+                            s.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        _ => {
+                            self.pos -= 1;
+                            return Err(self.error("invalid escape"));
+                        }
+                    };
+                    self.pos += 1;
+                    s.push(escaped);
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = self.rest().get(..4)?;
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        self.pos += 4;
+        u32::from_str_radix(digits, 16).ok()
+    }
+
+    // After \u.  Errors point at the backslash.
+    fn unicode_escape(&mut self) -> Result<char, JsonPathError> {
+        let start = self.pos - 2;
+        let c = match self.hex4() {
+            Some(hi @ 0xD800..=0xDBFF) => {
+                let lo = if self.eat_str("\\u") {
+                    self.hex4()
+                } else {
+                    None
+                };
+                lo.filter(|lo| (0xDC00..=0xDFFF).contains(lo))
+                    .map(|lo| 0x10000 + ((hi - 0xD800) << 10) + (lo - 0xDC00))
+            }
+            c => c,
+        };
+        // A lone low surrogate isn't a char either.
+        match c.and_then(char::from_u32) {
+            Some(c) => Ok(c),
+            None => {
+                self.pos = start;
+                Err(self.error("invalid unicode escape"))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Number, JsonPathError> {
+        let start = self.pos;
+        let invalid = |p: &mut Parser| {
+            p.pos = start;
+            Err(p.error("invalid number"))
+        };
+
+        self.eat('-');
+        let digits = self.rest().bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 || (digits > 1 && self.rest().starts_with('0')) {
+            return invalid(self);
+        }
+        self.pos += digits;
+        if self.eat('.') {
+            let digits = self.rest().bytes().take_while(u8::is_ascii_digit).count();
+            if digits == 0 {
+                return invalid(self);
+            }
+            self.pos += digits;
+        }
+        if self.eat('e') || self.eat('E') {
+            if !self.eat('+') {
+                self.eat('-');
+            }
+            let digits = self.rest().bytes().take_while(u8::is_ascii_digit).count();
+            if digits == 0 {
+                return invalid(self);
+            }
+            self.pos += digits;
+        }
+
+        match Number::from_lexeme(&self.s[start..self.pos]) {
+            Some(n) => Ok(n.without_text()),
+            None => invalid(self),
+        }
+    }
+
+    fn logical_or(&mut self) -> Result<Expr, JsonPathError> {
+        let mut terms = vec![self.logical_and()?];
+        loop {
+            let before = self.pos;
+            self.skip_blank();
+            if !self.eat_str("||") {
+                self.pos = before;
+                break;
+            }
+            self.skip_blank();
+            terms.push(self.logical_and()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Expr::Or(terms)
+        })
+    }
+
+    fn logical_and(&mut self) -> Result<Expr, JsonPathError> {
+        let mut terms = vec![self.basic()?];
+        loop {
+            let before = self.pos;
+            self.skip_blank();
+            if !self.eat_str("&&") {
+                self.pos = before;
+                break;
+            }
+            self.skip_blank();
+            terms.push(self.basic()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Expr::And(terms)
+        })
+    }
+
+    fn parenthesized(&mut self) -> Result<Expr, JsonPathError> {
+        self.expect('(')?;
+        self.nest()?;
+        self.skip_blank();
+        let expr = self.logical_or()?;
+        self.skip_blank();
+        self.expect(')')?;
+        self.nesting -= 1;
+        Ok(expr)
+    }
+
+    fn basic(&mut self) -> Result<Expr, JsonPathError> {
+        if self.eat('!') {
+            self.skip_blank();
+            let expr = if self.peek() == Some('(') {
+                self.parenthesized()?
+            } else {
+                let start = self.pos;
+                let operand = self.operand()?;
+                self.test(operand, start)?
+            };
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        if self.peek() == Some('(') {
+            return self.parenthesized();
+        }
+
+        let start = self.pos;
+        let left = self.operand()?;
+        let before = self.pos;
+        self.skip_blank();
+        let op = [
+            ("==", CompareOp::Eq),
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+        ]
+        .into_iter()
+        .find(|(text, _)| self.eat_str(text));
+
+        match op {
+            Some((_, op)) => {
+                let left = self.comparable(left, start)?;
+                self.skip_blank();
+                let right_start = self.pos;
+                let right = self.operand()?;
+                let right = self.comparable(right, right_start)?;
+                Ok(Expr::Compare(left, op, right))
+            }
+            None => {
+                self.pos = before;
+                self.test(left, start)
+            }
+        }
+    }
+
+    // An operand used on its own as a test, which was parsed starting at start.
+    fn test(&mut self, operand: Operand, start: usize) -> Result<Expr, JsonPathError> {
+        match operand {
+            Operand::Query(q) => Ok(Expr::Exists(q)),
+            Operand::Function(f) if !f.returns_value() => Ok(Expr::Test(f)),
+            _ => {
+                self.pos = start;
+                Err(self.error("value must be compared"))
+            }
+        }
+    }
+
+    // Checks that operand, parsed starting at start, can be compared.
+    fn comparable(&mut self, operand: Operand, start: usize) -> Result<Comparable, JsonPathError> {
+        match operand {
+            Operand::Literal(v) => Ok(Comparable::Literal(v)),
+            Operand::Query(q) if q.is_singular() => Ok(Comparable::Query(q)),
+            Operand::Function(f) if f.returns_value() => Ok(Comparable::Function(f)),
+            Operand::Query(_) => {
+                self.pos = start;
+                Err(self.error("query must select at most one value to be compared"))
+            }
+            Operand::Function(_) => {
+                self.pos = start;
+                Err(self.error("function does not return a value"))
+            }
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, JsonPathError> {
+        match self.peek() {
+            Some('@') => {
+                self.pos += 1;
+                Ok(Operand::Query(FilterQuery {
+                    relative: true,
+                    query: self.query()?,
+                }))
+            }
+            Some('$') => {
+                self.pos += 1;
+                Ok(Operand::Query(FilterQuery {
+                    relative: false,
+                    query: self.query()?,
+                }))
+            }
+            Some(quote @ ('\'' | '"')) => {
+                self.pos += 1;
+                Ok(Operand::Literal(Value::String(self.string(quote)?)))
+            }
+            Some('-' | '0'..='9') => Ok(Operand::Literal(Value::Number(self.number()?))),
+            Some('a'..='z') => {
+                let start = self.pos;
+                let len = self
+                    .rest()
+                    .find(|c: char| !matches!(c, 'a'..='z' | '0'..='9' | '_'))
+                    .unwrap_or(self.rest().len());
+                let name = &self.rest()[..len];
+                self.pos += len;
+                if self.peek() == Some('(') {
+                    return Ok(Operand::Function(self.function(name, start)?));
+                }
+                match name {
+                    "true" => Ok(Operand::Literal(Value::Boolean(true))),
+                    "false" => Ok(Operand::Literal(Value::Boolean(false))),
+                    "null" => Ok(Operand::Literal(Value::Null)),
+                    _ => {
+                        self.pos = start;
+                        Err(self.error("expected a value"))
+                    }
+                }
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    // After the function's name, which started at start.
+    fn function(&mut self, name: &str, start: usize) -> Result<Function, JsonPathError> {
+        self.expect('(')?;
+        self.nest()?;
+        let mut args = Vec::new();
+        self.skip_blank();
+        if !self.eat(')') {
+            loop {
+                let arg_start = self.pos;
+                args.push((self.operand()?, arg_start));
+                self.skip_blank();
+                if self.eat(')') {
+                    break;
+                }
+                if !self.eat(',') {
+                    return Err(self.error("expected ',' or ')'"));
+                }
+                self.skip_blank();
+            }
+        }
+        self.nesting -= 1;
+
+        let arity = match name {
+            "length" | "count" | "value" => 1,
+            "match" | "search" => 2,
+            _ => {
+                self.pos = start;
+                return Err(self.error(format!("unknown function {}", name)));
+            }
+        };
+        if args.len() != arity {
+            self.pos = start;
+            return Err(self.error(format!("{} takes {} argument(s)", name, arity)));
+        }
+
+        let f = match name {
+            "length" => {
+                let (operand, arg_start) = args.remove(0);
+                Function::Length(Box::new(self.comparable(operand, arg_start)?))
+            }
+            "count" | "value" => {
+                let q = match args.remove(0) {
+                    (Operand::Query(q), _) => q,
+                    (_, arg_start) => {
+                        self.pos = arg_start;
+                        return Err(self.error("expected a query"));
+                    }
+                };
+                if name == "count" {
+                    Function::Count(q)
+                } else {
+                    Function::Value(q)
+                }
+            }
+            _ => {
+                let (operand, arg_start) = args.remove(0);
+                let subject = Box::new(self.comparable(operand, arg_start)?);
+                let (operand, arg_start) = args.remove(0);
+                let pattern = Box::new(match self.comparable(operand, arg_start)? {
+                    Comparable::Literal(Value::String(ref p)) => Pattern::Compiled(Regex::new(p)),
+                    pattern => Pattern::Dynamic(pattern),
+                });
+                if name == "match" {
+                    Function::Match(subject, pattern)
+                } else {
+                    Function::Search(subject, pattern)
+                }
+            }
+        };
+        Ok(f)
+    }
+}
+
+impl JsonPath {
+    pub fn parse(s: &str) -> Result<JsonPath, JsonPathError> {
+        let mut parser = Parser {
+            s,
+            pos: 0,
+            nesting: 0,
+        };
+        parser.expect('$')?;
+        let query = parser.query()?;
+        if parser.pos < s.len() {
+            return Err(parser.error("unexpected character"));
+        }
+        Ok(JsonPath {
+            source: s.to_owned(),
+            query,
+        })
+    }
+
+    /// Every value the query selects, in order, along with its path.
+    pub fn query<'v>(&self, v: &'v Value) -> Vec<Node<'v>> {
+        select(v, &self.query, vec![(v, Vec::new())])
+            .into_iter()
+            .map(|(value, path)| Node { path, value })
+            .collect()
+    }
+
+    /// Like query, but without working out paths.
+    pub fn query_values<'v>(&self, v: &'v Value) -> Vec<&'v Value> {
+        select(v, &self.query, vec![(v, ())])
+            .into_iter()
+            .map(|(value, _)| value)
+            .collect()
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = JsonPathError;
+
+    fn from_str(s: &str) -> Result<JsonPath, JsonPathError> {
+        JsonPath::parse(s)
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Value {
+    /// Runs a JSONPath query, eg. `v.query("$..name")`.  Use JsonPath to run the same query
+    /// more than once without parsing it again.
+    pub fn query(&self, path: &str) -> Result<Vec<Node<'_>>, JsonPathError> {
+        Ok(JsonPath::parse(path)?.query(self))
+    }
+}
+
+// Tracks where each selected node is, or doesn't bother when that isn't needed.
+trait Trail: Clone {
+    fn key(&self, k: &str) -> Self;
+    fn index(&self, i: usize) -> Self;
+}
+
+impl Trail for () {
+    fn key(&self, _: &str) {}
+    fn index(&self, _: usize) {}
+}
+
+impl Trail for Vec<PathSegment> {
+    fn key(&self, k: &str) -> Self {
+        let mut path = self.clone();
+        path.push(PathSegment::Key(k.to_owned()));
+        path
+    }
+
+    fn index(&self, i: usize) -> Self {
+        let mut path = self.clone();
+        path.push(PathSegment::Index(i));
+        path
+    }
+}
+
+fn children<'v, T: Trail>(v: &'v Value, trail: &T) -> Vec<(&'v Value, T)> {
+    match v {
+        Value::Array(a) => a
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c, trail.index(i)))
+            .collect(),
+        Value::Object(o) => o.iter().map(|(k, c)| (c, trail.key(k))).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn select<'v, T: Trail>(
+    root: &'v Value,
+    query: &Query,
+    start: Vec<(&'v Value, T)>,
+) -> Vec<(&'v Value, T)> {
+    let mut nodes = start;
+    for segment in &query.segments {
+        let mut next = Vec::new();
+        for (v, trail) in &nodes {
+            if !segment.descendant {
+                for selector in &segment.selectors {
+                    apply(root, selector, v, trail, &mut next);
+                }
+                continue;
+            }
+
+            // Visit the node and everything under it, parents before children.  This doesn't
+            // recurse, so deep documents can't overflow the stack.
+            let mut pending = vec![(*v, trail.clone())];
+            while let Some((d, trail)) = pending.pop() {
+                for selector in &segment.selectors {
+                    apply(root, selector, d, &trail, &mut next);
+                }
+                pending.extend(children(d, &trail).into_iter().rev());
+            }
+        }
+        nodes = next;
+    }
+    nodes
+}
+
+// Normalizes a possibly negative index into an array of len elements.
+fn array_index(i: i64, len: usize) -> Option<usize> {
+    let i = if i < 0 { len as i64 + i } else { i };
+    usize::try_from(i).ok().filter(|&i| i < len)
+}
+
+fn slice_indices(
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+    len: usize,
+) -> Vec<usize> {
+    let step = step.unwrap_or(1);
+    let len = len as i64;
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+
+    let mut res = Vec::new();
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).clamp(0, len);
+        let upper = normalize(end.unwrap_or(len)).clamp(0, len);
+        let mut i = lower;
+        while i < upper {
+            res.push(i as usize);
+            i += step;
+        }
+    } else if step < 0 {
+        let upper = normalize(start.unwrap_or(len - 1)).clamp(-1, len - 1);
+        let lower = normalize(end.unwrap_or(-len - 1)).clamp(-1, len - 1);
+        let mut i = upper;
+        while lower < i {
+            res.push(i as usize);
+            i += step;
+        }
+    }
+    res
+}
+
+fn apply<'v, T: Trail>(
+    root: &'v Value,
+    selector: &Selector,
+    v: &'v Value,
+    trail: &T,
+    out: &mut Vec<(&'v Value, T)>,
+) {
+    match (selector, v) {
+        (Selector::Name(name), Value::Object(o)) => {
+            if let Some(c) = o.get(name) {
+                out.push((c, trail.key(name)));
+            }
+        }
+        (Selector::Wildcard, _) => out.extend(children(v, trail)),
+        (Selector::Index(i), Value::Array(a)) => {
+            if let Some(i) = array_index(*i, a.len()) {
+                out.push((&a[i], trail.index(i)));
+            }
+        }
+        (Selector::Slice(start, end, step), Value::Array(a)) => {
+            for i in slice_indices(*start, *end, *step, a.len()) {
+                out.push((&a[i], trail.index(i)));
+            }
+        }
+        (Selector::Filter(expr), _) => {
+            for (c, trail) in children(v, trail) {
+                if evaluate(root, expr, c) {
+                    out.push((c, trail));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn run_filter_query<'v>(root: &'v Value, q: &FilterQuery, current: &'v Value) -> Vec<&'v Value> {
+    let start = if q.relative { current } else { root };
+    select(root, &q.query, vec![(start, ())])
+        .into_iter()
+        .map(|(v, _)| v)
+        .collect()
+}
+
+fn evaluate(root: &Value, expr: &Expr, current: &Value) -> bool {
+    match expr {
+        Expr::Or(terms) => terms.iter().any(|e| evaluate(root, e, current)),
+        Expr::And(terms) => terms.iter().all(|e| evaluate(root, e, current)),
+        Expr::Not(e) => !evaluate(root, e, current),
+        Expr::Exists(q) => !run_filter_query(root, q, current).is_empty(),
+        Expr::Test(f) => test_function(root, f, current),
+        Expr::Compare(a, op, b) => {
+            let a = comparable_value(root, a, current);
+            let b = comparable_value(root, b, current);
+            let (a, b) = (a.as_deref(), b.as_deref());
+            match op {
+                CompareOp::Eq => a == b,
+                CompareOp::Ne => a != b,
+                CompareOp::Lt => less(a, b),
+                CompareOp::Le => less(a, b) || a == b,
+                CompareOp::Gt => less(b, a),
+                CompareOp::Ge => less(b, a) || a == b,
+            }
+        }
+    }
+}
+
+fn less(a: Option<&Value>, b: Option<&Value>) -> bool {
+    match (a, b) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a < b,
+        (Some(Value::String(a)), Some(Value::String(b))) => a < b,
+        _ => false,
+    }
+}
+
+// None is the RFC's Nothing: a query that selected nothing, or a function with no result.
+fn comparable_value<'a>(
+    root: &'a Value,
+    c: &'a Comparable,
+    current: &'a Value,
+) -> Option<Cow<'a, Value>> {
+    match c {
+        Comparable::Literal(v) => Some(Cow::Borrowed(v)),
+        Comparable::Query(q) => run_filter_query(root, q, current)
+            .first()
+            .map(|v| Cow::Borrowed(*v)),
+        Comparable::Function(f) => value_function(root, f, current),
+    }
+}
+
+fn value_function<'a>(
+    root: &'a Value,
+    f: &'a Function,
+    current: &'a Value,
+) -> Option<Cow<'a, Value>> {
+    let number = |n: usize| Some(Cow::Owned(Value::Number(Number::from(n as u64))));
+    match f {
+        Function::Length(arg) => match comparable_value(root, arg, current)?.as_ref() {
+            Value::String(s) => number(s.chars().count()),
+            Value::Array(a) => number(a.len()),
+            Value::Object(o) => number(o.len()),
+            _ => None,
+        },
+        Function::Count(q) => number(run_filter_query(root, q, current).len()),
+        Function::Value(q) => match run_filter_query(root, q, current).as_slice() {
+            [v] => Some(Cow::Borrowed(*v)),
+            _ => None,
+        },
+        Function::Match(..) | Function::Search(..) => None,
+    }
+}
+
+fn test_function(root: &Value, f: &Function, current: &Value) -> bool {
+    let (subject, pattern, whole) = match f {
+        Function::Match(subject, pattern) => (subject, pattern, true),
+        Function::Search(subject, pattern) => (subject, pattern, false),
+        _ => return false,
+    };
+
+    let subject = comparable_value(root, subject, current);
+    let s = match subject.as_deref() {
+        Some(Value::String(s)) => s,
+        _ => return false,
+    };
+
+    let dynamic;
+    let regex = match pattern.as_ref() {
+        Pattern::Compiled(regex) => regex.as_ref(),
+        Pattern::Dynamic(pattern) => {
+            dynamic = match comparable_value(root, pattern, current).as_deref() {
+                Some(Value::String(p)) => Regex::new(p),
+                _ => None,
+            };
+            dynamic.as_ref()
+        }
+    };
+
+    match regex {
+        Some(regex) if whole => regex.is_match(s),
+        Some(regex) => regex.is_found(s),
+        None => false,
+    }
+}
@@ -0,0 +1,428 @@
+// I-Regexp (RFC 9485), the interoperable regular expressions that JSONPath's match and search
+// functions use.  Patterns are compiled to a small NFA and run a step at a time over every live
+// state at once, so matching is linear in the length of the input and can't blow up.
+//
+// Category escapes like \p{Lu} take any of the general categories RFC 9485 lists, or one of their
+// one letter groups like \p{L}.
+
+mod categories;
+
+use categories::category_of;
+
+// Bounds how far counted repetition like (a{100}){100} can expand.
+const MAX_INSTRUCTIONS: usize = 10_000;
+const MAX_REPEAT: u32 = 1000;
+const MAX_GROUP_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    // A general category or group of them, or everything outside it.
+    Category(&'static str, bool),
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let found = self.items.iter().any(|item| match *item {
+            ClassItem::Range(lo, hi) => lo <= c && c <= hi,
+            ClassItem::Category(name, negated) => category_of(c).starts_with(name) != negated,
+        });
+        found != self.negated
+    }
+}
+
+enum Node {
+    Char(char),
+    Class(Class),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat(Box<Node>, u32, Option<u32>),
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Class(Class),
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Regex {
+    program: Vec<Inst>,
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    depth: usize,
+}
+
+// The categories RFC 9485 allows in \p{..}.  A one letter name is every category starting with it.
+const CATEGORIES: &[&str] = &[
+    "L", "Lu", "Ll", "Lt", "Lm", "Lo", "M", "Mn", "Mc", "Me", "N", "Nd", "Nl", "No", "P", "Pc",
+    "Pd", "Ps", "Pe", "Pi", "Pf", "Po", "Z", "Zs", "Zl", "Zp", "S", "Sm", "Sc", "Sk", "So", "C",
+    "Cc", "Cf", "Co", "Cn",
+];
+
+fn category(name: &str) -> Option<&'static str> {
+    CATEGORIES.iter().copied().find(|&c| c == name)
+}
+
+impl<'a> Parser<'a> {
+    fn eat(&mut self, c: char) -> bool {
+        self.chars.next_if_eq(&c).is_some()
+    }
+
+    fn alternation(&mut self) -> Option<Node> {
+        let mut branches = vec![self.branch()?];
+        while self.eat('|') {
+            branches.push(self.branch()?);
+        }
+        Some(if branches.len() == 1 {
+            branches.pop()?
+        } else {
+            Node::Alternate(branches)
+        })
+    }
+
+    fn branch(&mut self) -> Option<Node> {
+        let mut pieces = Vec::new();
+        while !matches!(self.chars.peek(), None | Some('|') | Some(')')) {
+            let atom = self.atom()?;
+            pieces.push(self.quantified(atom)?);
+        }
+        Some(Node::Concat(pieces))
+    }
+
+    fn quantified(&mut self, atom: Node) -> Option<Node> {
+        let (min, max) = match self.chars.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                self.chars.next();
+                let min = self.count()?;
+                let max = if self.eat(',') {
+                    if self.chars.peek() == Some(&'}') {
+                        None
+                    } else {
+                        Some(self.count()?)
+                    }
+                } else {
+                    Some(min)
+                };
+                if !self.eat('}') || max.is_some_and(|max| max < min) {
+                    return None;
+                }
+                return Some(Node::Repeat(Box::new(atom), min, max));
+            }
+            _ => return Some(atom),
+        };
+        self.chars.next();
+        Some(Node::Repeat(Box::new(atom), min, max))
+    }
+
+    fn count(&mut self) -> Option<u32> {
+        let mut n: u32 = 0;
+        let mut digits = 0;
+        while let Some(d) = self.chars.peek().and_then(|c| c.to_digit(10)) {
+            self.chars.next();
+            n = n.saturating_mul(10).saturating_add(d);
+            digits += 1;
+        }
+        (digits > 0 && n <= MAX_REPEAT).then_some(n)
+    }
+
+    fn atom(&mut self) -> Option<Node> {
+        match self.chars.next()? {
+            '(' => {
+                self.depth += 1;
+                if self.depth > MAX_GROUP_DEPTH {
+                    return None;
+                }
+                let node = self.alternation()?;
+                self.depth -= 1;
+                self.eat(')').then_some(node)
+            }
+            '[' => self.class().map(Node::Class),
+            '.' => Some(Node::Class(Class {
+                negated: true,
+                items: vec![ClassItem::Range('\n', '\n'), ClassItem::Range('\r', '\r')],
+            })),
+            '\\' => match self.escape()? {
+                ClassItem::Range(c, _) => Some(Node::Char(c)),
+                category => Some(Node::Class(Class {
+                    negated: false,
+                    items: vec![category],
+                })),
+            },
+            ')' | '*' | '+' | '?' | ']' | '{' | '}' | '|' => None,
+            c => Some(Node::Char(c)),
+        }
+    }
+
+    // After a backslash: a single character escape as a one character range, or a category.
+    fn escape(&mut self) -> Option<ClassItem> {
+        let c = match self.chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            c @ ('(' | ')' | '*' | '+' | '-' | '.' | '?' | '[' | '\\' | ']' | '^' | '{' | '|'
+            | '}') => c,
+            p @ ('p' | 'P') => {
+                if !self.eat('{') {
+                    return None;
+                }
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|&c| c != '}') {
+                    name.push(c);
+                }
+                if !self.eat('}') {
+                    return None;
+                }
+                return Some(ClassItem::Category(category(&name)?, p == 'P'));
+            }
+            _ => return None,
+        };
+        Some(ClassItem::Range(c, c))
+    }
+
+    // A character inside brackets, which may be one end of a range.
+    fn class_char(&mut self) -> Option<char> {
+        match self.chars.next()? {
+            '\\' => match self.escape()? {
+                ClassItem::Range(c, _) => Some(c),
+                ClassItem::Category(..) => None,
+            },
+            '-' | '[' | ']' => None,
+            c => Some(c),
+        }
+    }
+
+    // After the opening bracket.
+    fn class(&mut self) -> Option<Class> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        if self.eat('-') {
+            items.push(ClassItem::Range('-', '-'));
+        }
+
+        loop {
+            match self.chars.peek()? {
+                ']' if !items.is_empty() => break,
+                '-' => {
+                    // Only allowed last.
+                    self.chars.next();
+                    items.push(ClassItem::Range('-', '-'));
+                    if self.chars.peek() != Some(&']') {
+                        return None;
+                    }
+                    break;
+                }
+                '\\' => {
+                    let mut lookahead = self.chars.clone();
+                    lookahead.next();
+                    if matches!(lookahead.peek(), Some('p' | 'P')) {
+                        self.chars.next();
+                        items.push(self.escape()?);
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+
+            let lo = self.class_char()?;
+            let mut lookahead = self.chars.clone();
+            let hi = if lookahead.next() == Some('-') && lookahead.peek() != Some(&']') {
+                self.chars.next();
+                self.class_char()?
+            } else {
+                lo
+            };
+            if hi < lo {
+                return None;
+            }
+            items.push(ClassItem::Range(lo, hi));
+        }
+
+        self.chars.next();
+        Some(Class { negated, items })
+    }
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Option<usize> {
+        if self.program.len() >= MAX_INSTRUCTIONS {
+            return None;
+        }
+        self.program.push(inst);
+        Some(self.program.len() - 1)
+    }
+
+    fn emit(&mut self, node: &Node) -> Option<()> {
+        match node {
+            Node::Char(c) => {
+                self.push(Inst::Char(*c))?;
+            }
+            Node::Class(class) => {
+                self.push(Inst::Class(class.clone()))?;
+            }
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.emit(node)?;
+                }
+            }
+            Node::Alternate(branches) => {
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 == branches.len() {
+                        self.emit(branch)?;
+                        break;
+                    }
+                    let split = self.push(Inst::Split(0, 0))?;
+                    self.emit(branch)?;
+                    jumps.push(self.push(Inst::Jump(0))?);
+                    self.program[split] = Inst::Split(split + 1, self.program.len());
+                }
+                let end = self.program.len();
+                for jump in jumps {
+                    self.program[jump] = Inst::Jump(end);
+                }
+            }
+            Node::Repeat(node, min, max) => {
+                for _ in 0..*min {
+                    self.emit(node)?;
+                }
+                match max {
+                    None => {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.emit(node)?;
+                        self.push(Inst::Jump(split))?;
+                        self.program[split] = Inst::Split(split + 1, self.program.len());
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.push(Inst::Split(0, 0))?);
+                            self.emit(node)?;
+                        }
+                        let end = self.program.len();
+                        for split in splits {
+                            self.program[split] = Inst::Split(split + 1, end);
+                        }
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+// Adds pc and everything reachable from it without consuming input to list.
+fn add_thread(
+    program: &[Inst],
+    list: &mut Vec<usize>,
+    seen: &mut [usize],
+    generation: usize,
+    pc: usize,
+) {
+    let mut stack = vec![pc];
+    while let Some(pc) = stack.pop() {
+        if seen[pc] == generation {
+            continue;
+        }
+        seen[pc] = generation;
+        match program[pc] {
+            Inst::Jump(to) => stack.push(to),
+            Inst::Split(a, b) => {
+                stack.push(b);
+                stack.push(a);
+            }
+            _ => list.push(pc),
+        }
+    }
+}
+
+impl Regex {
+    /// None if the pattern isn't a valid I-Regexp, or uses something unsupported.
+    pub(crate) fn new(pattern: &str) -> Option<Regex> {
+        let mut parser = Parser {
+            chars: pattern.chars().peekable(),
+            depth: 0,
+        };
+        let node = parser.alternation()?;
+        if parser.chars.next().is_some() {
+            // An unmatched closing parenthesis.
+            return None;
+        }
+
+        let mut compiler = Compiler {
+            program: Vec::new(),
+        };
+        compiler.emit(&node)?;
+        compiler.push(Inst::Match)?;
+        Some(Regex {
+            program: compiler.program,
+        })
+    }
+
+    /// Whether the whole of s matches.
+    pub(crate) fn is_match(&self, s: &str) -> bool {
+        self.run(s, false)
+    }
+
+    /// Whether some substring of s matches.
+    pub(crate) fn is_found(&self, s: &str) -> bool {
+        self.run(s, true)
+    }
+
+    fn run(&self, s: &str, anywhere: bool) -> bool {
+        let program = &self.program;
+        let mut seen = vec![usize::MAX; program.len()];
+        let mut current = Vec::new();
+        let mut next = Vec::new();
+        let is_match = |list: &[usize]| list.iter().any(|&pc| matches!(program[pc], Inst::Match));
+
+        add_thread(program, &mut current, &mut seen, 0, 0);
+        for (generation, c) in s.chars().enumerate() {
+            if anywhere {
+                add_thread(program, &mut current, &mut seen, generation, 0);
+                if is_match(&current) {
+                    return true;
+                }
+            } else if current.is_empty() {
+                return false;
+            }
+
+            next.clear();
+            for &pc in &current {
+                let step = match &program[pc] {
+                    Inst::Char(x) => *x == c,
+                    Inst::Class(class) => class.matches(c),
+                    _ => false,
+                };
+                if step {
+                    add_thread(program, &mut next, &mut seen, generation + 1, pc + 1);
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+        }
+
+        if anywhere {
+            let generation = s.chars().count();
+            add_thread(program, &mut current, &mut seen, generation, 0);
+        }
+        is_match(&current)
+    }
+}
@@ -0,0 +1,396 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+use std::ops::{Index, IndexMut};
+use std::slice;
+use std::vec;
+
+use crate::fortunate_json::Value;
+
+const EMPTY: usize = usize::MAX;
+
+// Maps this small are searched linearly and don't build a table at all.
+const LINEAR_LIMIT: usize = 8;
+
+/// The members of a JSON object, kept in the order they were inserted.
+///
+/// Lookup is by hash once the map grows past a handful of entries.  Removing an entry keeps the
+/// order of the rest, and so costs O(n).
+///
+/// Equality ignores order, so two objects with the same members are equal however they were
+/// written.
+///
+/// Value::Object always uses this map.  Choosing a sorted or hashed backend for Values was left
+/// out on purpose: it would mean a type parameter on Value and on everything that takes one.  To
+/// get sorted or hashed objects, decode into a BTreeMap or HashMap instead, or call `sort_keys`.
+#[derive(Clone)]
+pub struct Map<K = String, V = Value> {
+    entries: Vec<(K, V)>,
+    // Open-addressed table of indices into entries.  Empty while the map is small enough to search
+    // linearly.
+    table: Vec<usize>,
+    hasher: RandomState,
+}
+
+impl<K, V> Map<K, V> {
+    pub fn new() -> Map<K, V> {
+        Map::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Map<K, V> {
+        Map {
+            entries: Vec::with_capacity(capacity),
+            table: Vec::new(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.table.clear();
+    }
+
+    /// The entry at a position in insertion order.
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries.get(index).map(|(k, v)| (k, v))
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.entries.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut(self.entries.iter_mut())
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    pub fn values_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut V> + ExactSizeIterator {
+        self.entries.iter_mut().map(|(_, v)| v)
+    }
+}
+
+impl<K: Hash + Eq, V> Map<K, V> {
+    fn slot<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        self.hasher.hash_one(key) as usize & (self.table.len() - 1)
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.table.is_empty() {
+            return self.entries.iter().position(|(k, _)| k.borrow() == key);
+        }
+
+        let mask = self.table.len() - 1;
+        let mut slot = self.slot(key);
+        loop {
+            match self.table[slot] {
+                EMPTY => return None,
+                i if self.entries[i].0.borrow() == key => return Some(i),
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+    }
+
+    // Puts entries[index] into the table, which must have a free slot.
+    fn place(&mut self, index: usize) {
+        let mask = self.table.len() - 1;
+        let mut slot = self.slot(&self.entries[index].0);
+        while self.table[slot] != EMPTY {
+            slot = (slot + 1) & mask;
+        }
+        self.table[slot] = index;
+    }
+
+    fn rebuild(&mut self) {
+        self.table.clear();
+        if self.entries.len() <= LINEAR_LIMIT {
+            return;
+        }
+
+        // Keep the table at most half full so that probe sequences stay short.
+        let size = (self.entries.len() * 2).next_power_of_two();
+        self.table.resize(size, EMPTY);
+        for i in 0..self.entries.len() {
+            self.place(i);
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).map(|i| &self.entries[i].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).map(|i| &mut self.entries[i].1)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key)
+            .map(|i| (&self.entries[i].0, &self.entries[i].1))
+    }
+
+    /// Where key is in insertion order.
+    pub fn index_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Adds a member at the end.  If the key is already present, its value is replaced in place
+    /// and the old value is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(i) = self.find(&key) {
+            return Some(mem::replace(&mut self.entries[i].1, value));
+        }
+
+        self.entries.push((key, value));
+        let len = self.entries.len();
+        if self.table.is_empty() {
+            if len > LINEAR_LIMIT {
+                self.rebuild();
+            }
+        } else if len * 2 > self.table.len() {
+            self.rebuild();
+        } else {
+            self.place(len - 1);
+        }
+
+        None
+    }
+
+    /// Removes a member, keeping the rest in order.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let i = self.find(key)?;
+        let entry = self.entries.remove(i);
+        self.rebuild();
+        Some(entry)
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let len = self.entries.len();
+        self.entries.retain_mut(|(k, v)| f(k, v));
+        if self.entries.len() != len {
+            self.rebuild();
+        }
+    }
+
+    /// Reorders the members by key.
+    pub fn sort_keys(&mut self)
+    where
+        K: Ord,
+    {
+        self.entries.sort_by(|a, b| a.0.cmp(&b.0));
+        self.rebuild();
+    }
+}
+
+impl<K, V> Default for Map<K, V> {
+    fn default() -> Map<K, V> {
+        Map::new()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Map<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V: PartialEq> PartialEq for Map<K, V> {
+    fn eq(&self, other: &Map<K, V>) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K, V, Q> Index<&Q> for Map<K, V>
+where
+    K: Borrow<Q> + Hash + Eq,
+    Q: Hash + Eq + ?Sized,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not present in Map")
+    }
+}
+
+impl<K, V, Q> IndexMut<&Q> for Map<K, V>
+where
+    K: Borrow<Q> + Hash + Eq,
+    Q: Hash + Eq + ?Sized,
+{
+    fn index_mut(&mut self, key: &Q) -> &mut V {
+        self.get_mut(key).expect("key not present in Map")
+    }
+}
+
+impl<K: Hash + Eq, V> Extend<(K, V)> for Map<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Map<K, V> {
+        let mut map = Map::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Hash + Eq, V, const N: usize> From<[(K, V); N]> for Map<K, V> {
+    fn from(entries: [(K, V); N]) -> Map<K, V> {
+        entries.into_iter().collect()
+    }
+}
+
+pub struct Iter<'a, K, V>(slice::Iter<'a, (K, V)>);
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(k, v)| (k, v))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+pub struct IterMut<'a, K, V>(slice::IterMut<'a, (K, V)>);
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (&*k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(k, v)| (&*k, v))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+pub struct IntoIter<K, V>(vec::IntoIter<(K, V)>);
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        self.0.next_back()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> IntoIterator for Map<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter(self.entries.into_iter())
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a Map<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut Map<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
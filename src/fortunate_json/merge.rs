@@ -0,0 +1,189 @@
+use crate::fortunate_json::{JsonPointer, Map, Value};
+
+/// How Value::merge combines two arrays.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ArrayMerge {
+    /// The overlay's array replaces the base's.
+    #[default]
+    Replace,
+    /// The overlay's elements are added to the end of the base's.
+    Concatenate,
+    /// Each element is merged with the one at the same index.  Extra overlay elements are added
+    /// to the end.
+    ByIndex,
+    /// Objects with the same value for this member are merged.  Overlay elements that don't match
+    /// one in the base, or that aren't objects with the member, are added to the end.
+    ByKey(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MergeOptions {
+    /// Defaults to ArrayMerge::Replace.
+    pub arrays: ArrayMerge,
+    /// Strategies for the arrays at particular pointers, which take precedence over arrays.
+    /// Pointers that go through an array use the element's index in the merged result.
+    pub array_overrides: Vec<(JsonPointer, ArrayMerge)>,
+    /// Whether a null in the overlay removes that member from the base object, as in a merge
+    /// patch.  Otherwise the null replaces the member's value.  Defaults to false.
+    pub null_removes: bool,
+}
+
+fn member<'v>(v: &'v Value, key: &str) -> Option<&'v Value> {
+    v.as_object().ok()?.get(key)
+}
+
+struct Merger<'a> {
+    options: &'a MergeOptions,
+    path: JsonPointer,
+}
+
+impl<'a> Merger<'a> {
+    fn strategy(&self) -> &'a ArrayMerge {
+        let overrides = &self.options.array_overrides;
+        match overrides.iter().find(|(path, _)| *path == self.path) {
+            Some((_, strategy)) => strategy,
+            None => &self.options.arrays,
+        }
+    }
+
+    fn merge_child(&mut self, token: String, base: &mut Value, overlay: Value) {
+        self.path.push(token);
+        self.merge(base, overlay);
+        self.path.pop();
+    }
+
+    fn merge(&mut self, base: &mut Value, mut overlay: Value) {
+        // Value implements Drop, so the overlay's contents have to be taken rather than moved.
+        match (base, &mut overlay) {
+            (Value::Object(base), Value::Object(overlay)) => {
+                for (k, v) in std::mem::take(overlay) {
+                    if self.options.null_removes && v == Value::Null {
+                        base.remove(&k);
+                        continue;
+                    }
+                    match base.get_mut(&k) {
+                        Some(b) => self.merge_child(k, b, v),
+                        None => {
+                            base.insert(k, v);
+                        }
+                    }
+                }
+            }
+            (Value::Array(base), Value::Array(overlay)) => match self.strategy() {
+                ArrayMerge::Replace => *base = std::mem::take(overlay),
+                ArrayMerge::Concatenate => base.append(overlay),
+                ArrayMerge::ByIndex => {
+                    for (i, v) in std::mem::take(overlay).into_iter().enumerate() {
+                        match base.get_mut(i) {
+                            Some(b) => self.merge_child(i.to_string(), b, v),
+                            None => base.push(v),
+                        }
+                    }
+                }
+                ArrayMerge::ByKey(field) => {
+                    for v in std::mem::take(overlay) {
+                        let i = member(&v, field)
+                            .and_then(|k| base.iter().position(|b| member(b, field) == Some(k)));
+                        match i {
+                            Some(i) => self.merge_child(i.to_string(), &mut base[i], v),
+                            None => base.push(v),
+                        }
+                    }
+                }
+            },
+            (base, _) => *base = overlay,
+        }
+    }
+}
+
+impl Value {
+    /// Merges overlay into this value, for layering configuration files.
+    ///
+    /// Objects are merged member by member, recursively.  Arrays are combined as options says.
+    /// Anything else in the overlay replaces what was in the base.
+    pub fn merge(&mut self, overlay: Value, options: &MergeOptions) {
+        let mut merger = Merger {
+            options,
+            path: JsonPointer::root(),
+        };
+        merger.merge(self, overlay);
+    }
+
+    /// Applies an RFC 7396 merge patch.
+    ///
+    /// An object patch sets each of its members in this value, removing those whose value is
+    /// null and merging objects recursively.  Any other patch replaces this value entirely.
+    pub fn merge_patch(&mut self, patch: &Value) {
+        let patch = match patch {
+            Value::Object(patch) => patch,
+            _ => {
+                *self = patch.clone();
+                return;
+            }
+        };
+
+        if !matches!(self, Value::Object(_)) {
+            *self = Value::Object(Map::new());
+        }
+        if let Value::Object(o) = self {
+            for (k, v) in patch {
+                if *v == Value::Null {
+                    o.remove(k);
+                    continue;
+                }
+                match o.get_mut(k) {
+                    Some(target) => target.merge_patch(v),
+                    None => {
+                        // Nulls inside a new member are removed too.
+                        let mut target = Value::Null;
+                        target.merge_patch(v);
+                        o.insert(k.clone(), target);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Makes a merge patch that turns a into b.
+///
+/// Merge patches can't set an object member to null, since null means remove, so members of b
+/// that are null come out missing.  Arrays are always replaced whole.
+pub fn merge_diff(a: &Value, b: &Value) -> Value {
+    let (a, b) = match (a, b) {
+        (Value::Object(a), Value::Object(b)) => (a, b),
+        _ => return b.clone(),
+    };
+
+    let mut patch = Map::new();
+    for k in a.keys() {
+        if b.get(k).is_none() {
+            patch.insert(k.clone(), Value::Null);
+        }
+    }
+    for (k, bv) in b {
+        match a.get(k) {
+            Some(av) if av == bv => {}
+            Some(av) => {
+                patch.insert(k.clone(), merge_diff(av, bv));
+            }
+            None => {
+                patch.insert(k.clone(), bv.clone());
+            }
+        }
+    }
+    Value::Object(patch)
+}
+
+/// Merges each layer onto the ones before it, eg. a base file, then per-platform overrides, then
+/// per-user overrides.  Null if there are no layers.
+pub fn merge_all<I>(layers: I, options: &MergeOptions) -> Value
+where
+    I: IntoIterator<Item = Value>,
+{
+    let mut res = Value::Null;
+    for layer in layers {
+        res.merge(layer, options);
+    }
+    res
+}
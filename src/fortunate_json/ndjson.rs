@@ -0,0 +1,243 @@
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+
+use crate::fortunate_json::parse::{parse_bytes_with, read_until_limited};
+use crate::fortunate_json::serialize::write_compact;
+use crate::fortunate_json::{
+    FromJSON, JSONError, ParseError, ParseErrorKind, ParseOptions, Position, ToJSON, Value,
+    WriterError,
+};
+
+/// A line that couldn't be read, parsed or decoded.
+#[derive(Debug, PartialEq)]
+pub struct NdjsonError {
+    /// 1-based.
+    pub line: usize,
+    /// Positions in a ParseError are relative to the whole input, not the line.
+    pub error: JSONError,
+}
+
+impl fmt::Display for NdjsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.error {
+            // Already says where it happened.
+            JSONError::ParseError(e) => e.fmt(f),
+            JSONError::DecodeError(e) => write!(f, "{} on line {}", e, self.line),
+        }
+    }
+}
+
+impl std::error::Error for NdjsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Reads newline-delimited JSON (also known as JSON Lines): one document per line.  Lines that
+/// are empty or only whitespace are ignored.
+///
+/// Iterating gives a Value for each line.  Use `decode` to get some other FromJSON type instead.
+pub struct NdjsonReader<R> {
+    source: R,
+    options: ParseOptions,
+    skip_invalid: bool,
+    buf: Vec<u8>,
+    // Where the next line starts.
+    line: usize,
+    offset: usize,
+    skipped: usize,
+    finished: bool,
+}
+
+impl<R: io::BufRead> NdjsonReader<R> {
+    pub fn new(source: R) -> NdjsonReader<R> {
+        NdjsonReader::with_options(source, ParseOptions::default())
+    }
+
+    /// Parses each line with options.  max_input_len applies to each line separately.
+    pub fn with_options(source: R, options: ParseOptions) -> NdjsonReader<R> {
+        NdjsonReader {
+            source,
+            options,
+            skip_invalid: false,
+            buf: Vec::new(),
+            line: 1,
+            offset: 0,
+            skipped: 0,
+            finished: false,
+        }
+    }
+
+    /// Whether to pass over lines that fail to parse or decode instead of returning an error for
+    /// them.  I/O errors are always returned.
+    pub fn skip_invalid_lines(mut self, skip: bool) -> NdjsonReader<R> {
+        self.skip_invalid = skip;
+        self
+    }
+
+    /// How many lines have been passed over by skip_invalid_lines.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Decodes each line as a T.
+    pub fn decode<T: FromJSON + Default>(self) -> Decoded<R, T> {
+        Decoded {
+            reader: self,
+            marker: PhantomData,
+        }
+    }
+
+    // Reads the next line that isn't blank into buf, returning where it started.
+    fn next_line(&mut self) -> Result<Option<Position>, NdjsonError> {
+        loop {
+            self.buf.clear();
+            let start = Position {
+                offset: self.offset,
+                line: self.line,
+                column: 1,
+            };
+
+            let mut skipped = 0;
+            let limit = self.options.max_input_len;
+            let read = read_until_limited(&mut self.source, b'\n', limit, &mut self.buf, |s| {
+                skipped += s.len()
+            });
+            let n = match read {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    let error = ParseError {
+                        kind: ParseErrorKind::Io(e.kind()),
+                        position: start,
+                    };
+                    return Err(NdjsonError {
+                        line: self.line,
+                        error: error.into(),
+                    });
+                }
+            };
+            if n == 0 {
+                return Ok(None);
+            }
+
+            self.line += 1;
+            self.offset += n + skipped;
+
+            if self.buf.iter().any(|b| !b.is_ascii_whitespace()) {
+                return Ok(Some(start));
+            }
+        }
+    }
+
+    fn next_with<T, F>(&mut self, convert: F) -> Option<Result<T, NdjsonError>>
+    where
+        F: Fn(Value) -> Result<T, JSONError>,
+    {
+        while !self.finished {
+            let start = match self.next_line() {
+                Ok(Some(start)) => start,
+                Ok(None) => break,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            };
+
+            let res = match parse_bytes_with(&self.buf, &self.options) {
+                Ok(v) => convert(v),
+                Err(e) => Err(ParseError {
+                    kind: e.kind,
+                    position: start.offset_by(e.position),
+                }
+                .into()),
+            };
+
+            match res {
+                Ok(v) => return Some(Ok(v)),
+                Err(_) if self.skip_invalid => self.skipped += 1,
+                Err(error) => {
+                    let line = start.line;
+                    return Some(Err(NdjsonError { line, error }));
+                }
+            }
+        }
+
+        self.finished = true;
+        None
+    }
+}
+
+impl<R: io::BufRead> Iterator for NdjsonReader<R> {
+    type Item = Result<Value, NdjsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with(Ok)
+    }
+}
+
+/// The iterator returned by NdjsonReader::decode.
+pub struct Decoded<R, T> {
+    reader: NdjsonReader<R>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<R, T> Decoded<R, T> {
+    pub fn reader(&self) -> &NdjsonReader<R> {
+        &self.reader
+    }
+}
+
+impl<R: io::BufRead, T: FromJSON + Default> Iterator for Decoded<R, T> {
+    type Item = Result<T, NdjsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_with(|v| {
+            let mut res = T::default();
+            T::from_json(&v, &mut res)?;
+            Ok(res)
+        })
+    }
+}
+
+/// Writes newline-delimited JSON: each value compact, on a line of its own.
+pub struct NdjsonWriter<W> {
+    out: W,
+    line: String,
+}
+
+impl<W: io::Write> NdjsonWriter<W> {
+    pub fn new(out: W) -> NdjsonWriter<W> {
+        NdjsonWriter {
+            out,
+            line: String::new(),
+        }
+    }
+
+    pub fn value<T>(&mut self, v: &T) -> Result<(), WriterError>
+    where
+        T: ToJSON + ?Sized,
+    {
+        self.write_value(&v.to_json())
+    }
+
+    /// Like value, but avoids converting a Value that already exists.
+    pub fn write_value(&mut self, v: &Value) -> Result<(), WriterError> {
+        self.line.clear();
+        // Compact output never contains a newline, since they are escaped in strings.
+        write_compact(&mut self.line, v).map_err(WriterError::Fmt)?;
+        self.line.push('\n');
+        self.out
+            .write_all(self.line.as_bytes())
+            .map_err(WriterError::Io)
+    }
+
+    pub fn flush(&mut self) -> Result<(), WriterError> {
+        self.out.flush().map_err(WriterError::Io)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
@@ -0,0 +1,309 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+// Largest integer magnitude an f64 can hold without rounding.
+const F64_EXACT_INT: u64 = 1 << 53;
+// Largest integer magnitude an f32 can hold without rounding.
+const F32_EXACT_INT: u64 = 1 << 24;
+
+#[derive(Debug, Clone, Copy)]
+enum N {
+    // Non-negative integers are always stored as PosInt so that equality is structural.
+    PosInt(u64),
+    NegInt(i64),
+    Float(f64),
+    // An integer too large for a u64 or an i64, rounded to the nearest f64.
+    Inexact(f64),
+}
+
+/// A JSON number.
+///
+/// Integers that fit in an `i64` or `u64` are kept exactly.  Everything else is an `f64`.
+/// Numbers produced by the parser also remember the text they were written as, and so do numbers
+/// made from an `f32`.
+#[derive(Debug, Clone)]
+pub struct Number {
+    n: N,
+    raw: Option<Box<str>>,
+}
+
+impl Number {
+    pub fn from_f64(f: f64) -> Number {
+        Number {
+            n: N::Float(f),
+            raw: None,
+        }
+    }
+
+    // Builds a Number from a lexeme that has already been validated by the lexer.
+    pub(crate) fn from_lexeme(s: &str) -> Option<Number> {
+        let is_integer = !s.contains(['.', 'e', 'E']);
+
+        let n = if is_integer {
+            if let Ok(u) = s.parse::<u64>() {
+                Some(N::PosInt(u))
+            } else if let Ok(i) = s.parse::<i64>() {
+                Some(if i >= 0 {
+                    N::PosInt(i as u64)
+                } else {
+                    N::NegInt(i)
+                })
+            } else {
+                Some(N::Inexact(s.parse::<f64>().ok()?))
+            }
+        } else {
+            None
+        };
+
+        let n = match n {
+            Some(n) => n,
+            None => N::Float(s.parse::<f64>().ok()?),
+        };
+
+        Some(Number {
+            n,
+            raw: Some(s.into()),
+        })
+    }
+
+    pub(crate) fn without_text(self) -> Number {
+        Number { raw: None, ..self }
+    }
+
+    /// The text this number is written as: what it was parsed from, if it came from the parser
+    /// and was standard JSON, or the shortest text for it if it was made from an f32.
+    pub fn as_str(&self) -> Option<&str> {
+        self.raw.as_deref()
+    }
+
+    // A float as an integer, if converting it can't lose anything.  That's when its text is
+    // exactly an integer, or with no text, when it is whole and every integer that close to zero
+    // has its own f64.
+    fn float_as_int(&self, f: f64) -> Option<i128> {
+        match &self.raw {
+            Some(raw) => lexeme_as_int(raw),
+            None if f.fract() == 0.0 && f.abs() <= F64_EXACT_INT as f64 => Some(f as i128),
+            None => None,
+        }
+    }
+
+    pub fn is_i64(&self) -> bool {
+        match self.n {
+            N::PosInt(u) => u <= i64::MAX as u64,
+            N::NegInt(_) => true,
+            N::Float(_) | N::Inexact(_) => false,
+        }
+    }
+
+    pub fn is_u64(&self) -> bool {
+        matches!(self.n, N::PosInt(_))
+    }
+
+    pub fn is_f64(&self) -> bool {
+        matches!(self.n, N::Float(_) | N::Inexact(_))
+    }
+
+    /// Returns None if the number is not an integer or does not fit in an i64.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.n {
+            N::PosInt(u) => i64::try_from(u).ok(),
+            N::NegInt(i) => Some(i),
+            N::Float(f) => i64::try_from(self.float_as_int(f)?).ok(),
+            N::Inexact(_) => None,
+        }
+    }
+
+    /// Returns None if the number is not an integer or does not fit in a u64.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.n {
+            N::PosInt(u) => Some(u),
+            N::NegInt(_) | N::Inexact(_) => None,
+            N::Float(f) => u64::try_from(self.float_as_int(f)?).ok(),
+        }
+    }
+
+    /// Returns None if the number is an integer too large to be represented exactly.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.n {
+            N::PosInt(u) if u <= F64_EXACT_INT => Some(u as f64),
+            N::NegInt(i) if i.unsigned_abs() <= F64_EXACT_INT => Some(i as f64),
+            N::Float(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Returns None if the number is an integer too large to be represented exactly, or a float
+    /// outside of the range of an f32.  Other floats are rounded to the nearest f32.
+    pub fn as_f32(&self) -> Option<f32> {
+        match self.n {
+            N::PosInt(u) if u <= F32_EXACT_INT => Some(u as f32),
+            N::NegInt(i) if i.unsigned_abs() <= F32_EXACT_INT => Some(i as f32),
+            N::Float(f) => {
+                let r = f as f32;
+                if r.is_infinite() && f.is_finite() {
+                    None
+                } else {
+                    Some(r)
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Converts to an f64, rounding if necessary.
+    pub fn to_f64(&self) -> f64 {
+        match self.n {
+            N::PosInt(u) => u as f64,
+            N::NegInt(i) => i as f64,
+            N::Float(f) | N::Inexact(f) => f,
+        }
+    }
+}
+
+impl PartialEq for Number {
+    // Numbers compare by value.  An integer and a float are equal if the float is exactly that
+    // integer.  The original text is not considered.
+    fn eq(&self, other: &Number) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    // Consistent with eq: integers are compared exactly, even against floats.  NaN is unordered.
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        fn int(n: N) -> Option<i128> {
+            match n {
+                N::PosInt(u) => Some(u as i128),
+                N::NegInt(i) => Some(i as i128),
+                N::Float(_) | N::Inexact(_) => None,
+            }
+        }
+
+        // Inexact integers are compared as the floats they were rounded to.
+        fn float(n: N) -> N {
+            match n {
+                N::Inexact(f) => N::Float(f),
+                n => n,
+            }
+        }
+
+        fn float_to_int(f: f64, i: i128) -> Option<Ordering> {
+            match f.partial_cmp(&(i as f64))? {
+                // f is a whole number within range of i, so this doesn't saturate.
+                Ordering::Equal => Some((f as i128).cmp(&i)),
+                o => Some(o),
+            }
+        }
+
+        match (float(self.n), float(other.n)) {
+            (N::Float(a), N::Float(b)) => a.partial_cmp(&b),
+            (N::Float(a), b) => float_to_int(a, int(b)?),
+            (a, N::Float(b)) => float_to_int(b, int(a)?).map(Ordering::reverse),
+            (a, b) => int(a)?.partial_cmp(&int(b)?),
+        }
+    }
+}
+
+impl From<u64> for Number {
+    fn from(u: u64) -> Number {
+        Number {
+            n: N::PosInt(u),
+            raw: None,
+        }
+    }
+}
+
+impl From<u32> for Number {
+    fn from(u: u32) -> Number {
+        Number::from(u as u64)
+    }
+}
+
+impl From<i64> for Number {
+    fn from(i: i64) -> Number {
+        if i >= 0 {
+            Number::from(i as u64)
+        } else {
+            Number {
+                n: N::NegInt(i),
+                raw: None,
+            }
+        }
+    }
+}
+
+impl From<i32> for Number {
+    fn from(i: i32) -> Number {
+        Number::from(i as i64)
+    }
+}
+
+impl From<f64> for Number {
+    fn from(f: f64) -> Number {
+        Number::from_f64(f)
+    }
+}
+
+impl From<f32> for Number {
+    // Remember the shortest text for the f32 so that 0.1f32 is written as 0.1 and not as
+    // 0.10000000149011612.
+    fn from(f: f32) -> Number {
+        Number {
+            n: N::Float(f as f64),
+            raw: if f.is_finite() {
+                Some(format!("{:?}", f).into())
+            } else {
+                None
+            },
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    // Writes the number as JSON text.  JSON has no way to represent NaN or infinity, so those
+    // are written as null.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(raw) = &self.raw {
+            return f.write_str(raw);
+        }
+
+        match self.n {
+            N::PosInt(u) => write!(f, "{}", u),
+            N::NegInt(i) => write!(f, "{}", i),
+            N::Float(n) | N::Inexact(n) if n.is_finite() => write!(f, "{:?}", n),
+            N::Float(_) | N::Inexact(_) => f.write_str("null"),
+        }
+    }
+}
+
+// The integer a number lexeme is exactly equal to, like 1500 for 1.5e3.  None if it isn't one,
+// or it is too large for an i128.
+fn lexeme_as_int(s: &str) -> Option<i128> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (mantissa, exp) = match s.split_once(['e', 'E']) {
+        Some((mantissa, exp)) => (mantissa, Some(exp)),
+        None => (s, None),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    let digits = format!("{}{}", int, frac);
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    let significant = digits.trim_end_matches('0');
+    let exp = exp.map_or(Some(0), |exp| exp.parse::<i64>().ok())? - frac.len() as i64
+        + (digits.len() - significant.len()) as i64;
+    if exp < 0 || significant.len() as i64 + exp > 39 {
+        return None;
+    }
+
+    let mut n = significant.parse::<i128>().ok()?;
+    for _ in 0..exp {
+        n = n.checked_mul(10)?;
+    }
+    Some(if negative { -n } else { n })
+}
@@ -1,12 +1,184 @@
-use std::collections::hash_map::HashMap;
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::Hash;
+use std::io::{self, BufRead, Read};
 
-use crate::fortunate_json::Value;
+use crate::fortunate_json::reader::{Event, EventParser};
+use crate::fortunate_json::{Map, Number, Value};
 
-#[derive(Debug, PartialEq)]
-pub struct ParseError(pub String);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    /// Byte offset from the start of the input.
+    pub offset: usize,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, counted in chars.
+    pub column: usize,
+}
+
+impl Position {
+    pub(crate) fn of(s: &[u8], offset: usize) -> Position {
+        let offset = offset.min(s.len());
+        let before = &s[..offset];
+
+        let line_start = match before.iter().rposition(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None => 0,
+        };
+
+        Position {
+            offset,
+            line: 1 + before.iter().filter(|&&b| b == b'\n').count(),
+            // Count everything except UTF-8 continuation bytes.
+            column: 1 + before[line_start..]
+                .iter()
+                .filter(|&&b| b & 0xC0 != 0x80)
+                .count(),
+        }
+    }
+}
+
+impl Position {
+    // The position just after bytes, if bytes started at self.
+    pub(crate) fn advanced_by(self, bytes: &[u8]) -> Position {
+        let mut res = self;
+        res.offset += bytes.len();
+        for &b in bytes {
+            if b == b'\n' {
+                res.line += 1;
+                res.column = 1;
+            } else if b & 0xC0 != 0x80 {
+                res.column += 1;
+            }
+        }
+        res
+    }
+
+    // Turns a position relative to some text that started at self into an absolute one.
+    pub(crate) fn offset_by(self, relative: Position) -> Position {
+        Position {
+            offset: self.offset + relative.offset,
+            line: self.line + relative.line - 1,
+            column: if relative.line == 1 {
+                self.column + relative.column - 1
+            } else {
+                relative.column
+            },
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnexpectedEof,
+    UnexpectedCharacter(char),
+    InvalidUtf8,
+    Io(std::io::ErrorKind),
+    UnterminatedString,
+    NewlineInString,
+    InvalidUnicodeEscape,
+    InvalidNumber,
+    UnknownIdentifier,
+    ExpectedValue,
+    ExpectedColon,
+    ExpectedCommaOrCloseBracket,
+    ExpectedCommaOrCloseBrace,
+    KeyMustBeString,
+    TrailingCharacters,
+    DuplicateKey { key: String, first: Position },
+    // The limits from ParseOptions.  Each holds the limit that was exceeded.
+    DepthLimitExceeded(usize),
+    InputTooLong(usize),
+    StringTooLong(usize),
+    ArrayTooLong(usize),
+    ObjectTooLong(usize),
+    TooManyNodes(usize),
+    // Only reported by Syntax::Strict.
+    ControlCharacterInString,
+    InvalidEscape(char),
+    LoneSurrogate,
+    // Only reported by Syntax::Relaxed.
+    UnterminatedComment,
+    // Only reported when reading RFC 7464 JSON text sequences.
+    MissingRecordSeparator,
+    TruncatedRecord,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedEof => write!(f, "Unexpected end of file"),
+            ParseErrorKind::UnexpectedCharacter(c) => write!(f, "Unexpected character {:?}", c),
+            ParseErrorKind::InvalidUtf8 => write!(f, "Invalid UTF-8"),
+            ParseErrorKind::Io(kind) => write!(f, "I/O error ({:?})", kind),
+            ParseErrorKind::UnterminatedString => {
+                write!(f, "Unexpected end of file while parsing string literal")
+            }
+            ParseErrorKind::NewlineInString => {
+                write!(f, "Unexpected newline while parsing string literal")
+            }
+            ParseErrorKind::InvalidUnicodeEscape => {
+                write!(f, "Malformed unicode escape in string literal")
+            }
+            ParseErrorKind::InvalidNumber => write!(f, "Malformed number"),
+            ParseErrorKind::UnknownIdentifier => write!(f, "Unknown identifier"),
+            ParseErrorKind::ExpectedValue => write!(f, "Expected a value"),
+            ParseErrorKind::ExpectedColon => write!(f, "Expected ':'"),
+            ParseErrorKind::ExpectedCommaOrCloseBracket => write!(f, "Expected ',' or ']'"),
+            ParseErrorKind::ExpectedCommaOrCloseBrace => write!(f, "Expected ',' or '}}'"),
+            ParseErrorKind::KeyMustBeString => write!(f, "Object keys must be strings"),
+            ParseErrorKind::TrailingCharacters => {
+                write!(f, "Unexpected characters after the end of the value")
+            }
+            ParseErrorKind::DuplicateKey { key, first } => {
+                write!(f, "Duplicate key {:?} (first defined at {})", key, first)
+            }
+            ParseErrorKind::DepthLimitExceeded(n) => {
+                write!(f, "Arrays and objects nested more than {} deep", n)
+            }
+            ParseErrorKind::InputTooLong(n) => write!(f, "Input longer than {} bytes", n),
+            ParseErrorKind::StringTooLong(n) => write!(f, "String longer than {} bytes", n),
+            ParseErrorKind::ArrayTooLong(n) => write!(f, "Array with more than {} elements", n),
+            ParseErrorKind::ObjectTooLong(n) => write!(f, "Object with more than {} members", n),
+            ParseErrorKind::TooManyNodes(n) => write!(f, "Document with more than {} values", n),
+            ParseErrorKind::ControlCharacterInString => {
+                write!(f, "Unescaped control character in string literal")
+            }
+            ParseErrorKind::InvalidEscape(c) => write!(f, "Invalid escape sequence \\{}", c),
+            ParseErrorKind::LoneSurrogate => write!(f, "Unpaired surrogate in unicode escape"),
+            ParseErrorKind::UnterminatedComment => {
+                write!(f, "Unexpected end of file while parsing comment")
+            }
+            ParseErrorKind::MissingRecordSeparator => {
+                write!(f, "Expected a record separator before the JSON text")
+            }
+            ParseErrorKind::TruncatedRecord => write!(f, "Record is truncated"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub position: Position,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
 
 #[derive(Debug, PartialEq)]
-enum Token<'a> {
+pub(crate) enum Token<'a> {
     OpenBracket,
     CloseBracket,
     OpenBrace,
@@ -14,21 +186,108 @@ enum Token<'a> {
     Colon,
     Comma,
     Identifier(&'a [u8]),
-    String(String),
-    Number(f32),
+    // Borrowed from the input unless the literal contained escapes.
+    String(Cow<'a, str>),
+    // A string with lone surrogates in it, as WTF-8.
+    RawString(Vec<u8>),
+    Number(Number),
+}
+
+/// Which dialect of JSON to accept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    /// RFC 8259, plus a few things that are unambiguous and commonly produced: numbers with
+    /// leading zeros or a trailing decimal point, raw control characters other than newline in
+    /// strings, and unknown escapes like \q, which stand for the escaped character.
+    #[default]
+    Lenient,
+    /// Exactly RFC 8259.
+    Strict,
+    /// Lenient plus the extensions of JSON5 (https://spec.json5.org), for files written by hand:
+    /// `//` and `/* */` comments, trailing commas, single-quoted strings, backslash-newline line
+    /// continuations and the JSON5 escapes, unquoted keys, hex integers, numbers with a leading
+    /// `+` or a leading or trailing decimal point, and `Infinity` and `NaN`.
+    ///
+    /// Unquoted keys are limited to ASCII letters, digits, `_` and `$`.  Infinity and NaN have
+    /// no JSON representation, so they are written back out as null.
+    Relaxed,
 }
 
-struct Lexer<'a> {
+// Whitespace that JSON5 allows besides JSON's: vertical tab, form feed, no-break space, byte order
+// mark, and the line and paragraph separators.
+const RELAXED_SPACES: &[&[u8]] = &[
+    b"\x0B",
+    b"\x0C",
+    "\u{A0}".as_bytes(),
+    "\u{FEFF}".as_bytes(),
+    "\u{2028}".as_bytes(),
+    "\u{2029}".as_bytes(),
+];
+
+pub(crate) struct Lexer<'a> {
     s: &'a [u8],
-    pos: usize,
+    pub(crate) pos: usize,
+    pub(crate) syntax: Syntax,
+    pub(crate) lone_surrogates: LoneSurrogates,
+    // Where the most recent token began and ended.
+    token_start: usize,
+    token_end: usize,
+    // Where a comment that runs to the end of the input began, if the last skip_whitespace
+    // stopped in one.  More input might finish it.
+    pub(crate) open_comment: Option<usize>,
 }
 
 impl<'a> Lexer<'a> {
-    fn new(s: &[u8]) -> Lexer {
-        Lexer { s: s, pos: 0 }
+    pub(crate) fn new(s: &[u8]) -> Lexer<'_> {
+        Lexer {
+            s,
+            pos: 0,
+            syntax: Syntax::Lenient,
+            lone_surrogates: LoneSurrogates::Error,
+            token_start: 0,
+            token_end: 0,
+            open_comment: None,
+        }
     }
 
-    fn eof(&self) -> bool {
+    pub(crate) fn with_options<'o>(s: &'o [u8], options: &ParseOptions) -> Lexer<'o> {
+        Lexer {
+            syntax: options.syntax,
+            lone_surrogates: options.lone_surrogates,
+            ..Lexer::new(s)
+        }
+    }
+
+    pub(crate) fn error_at(&self, kind: ParseErrorKind, offset: usize) -> ParseError {
+        ParseError {
+            kind,
+            position: Position::of(self.s, offset),
+        }
+    }
+
+    pub(crate) fn error(&self, kind: ParseErrorKind) -> ParseError {
+        self.error_at(kind, self.pos)
+    }
+
+    // An error about the most recent token as a whole.
+    pub(crate) fn token_error(&self, kind: ParseErrorKind) -> ParseError {
+        self.error_at(kind, self.token_start)
+    }
+
+    pub(crate) fn token_start(&self) -> usize {
+        self.token_start
+    }
+
+    // Where the most recent token ended, not counting the whitespace after it.
+    pub(crate) fn token_end(&self) -> usize {
+        self.token_end
+    }
+
+    pub(crate) fn input(&self) -> &'a [u8] {
+        self.s
+    }
+
+    pub(crate) fn eof(&self) -> bool {
         self.pos >= self.s.len()
     }
 
@@ -46,6 +305,13 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    fn peek_char(&self) -> Option<char> {
+        let end = (self.pos + 4).min(self.s.len());
+        String::from_utf8_lossy(&self.s[self.pos..end])
+            .chars()
+            .next()
+    }
+
     fn take_while<T>(&mut self, pred: T) -> &'a [u8]
     where
         T: Fn(u8) -> bool,
@@ -62,31 +328,91 @@ impl<'a> Lexer<'a> {
         &self.s[start_pos..self.pos]
     }
 
-    fn skip_whitespace(&mut self) {
-        self.take_while(|ch| ch == b' ' || ch == b'\t' || ch == b'\r' || ch == b'\n');
+    fn rest(&self) -> &'a [u8] {
+        self.s.get(self.pos..).unwrap_or_default()
+    }
+
+    // Also skips comments under Syntax::Relaxed.  An unterminated block comment is left for
+    // token to report.
+    pub(crate) fn skip_whitespace(&mut self) {
+        self.open_comment = None;
+        loop {
+            self.take_while(|ch| ch == b' ' || ch == b'\t' || ch == b'\r' || ch == b'\n');
+            if self.syntax != Syntax::Relaxed {
+                return;
+            }
+
+            let rest = self.rest();
+            let skip = if rest.starts_with(b"//") {
+                match rest.iter().position(|&b| b == b'\n' || b == b'\r') {
+                    Some(i) => i,
+                    None => {
+                        self.open_comment = Some(self.pos);
+                        rest.len()
+                    }
+                }
+            } else if rest.starts_with(b"/*") {
+                match rest[2..].windows(2).position(|w| w == b"*/") {
+                    Some(i) => i + 4,
+                    None => {
+                        self.open_comment = Some(self.pos);
+                        return;
+                    }
+                }
+            } else {
+                RELAXED_SPACES
+                    .iter()
+                    .find(|space| rest.starts_with(space))
+                    .map_or(0, |space| space.len())
+            };
+
+            if skip == 0 {
+                return;
+            }
+            self.pos += skip;
+        }
+    }
+
+    // Whether the rest of the input could be the start of a comment or whitespace that
+    // skip_whitespace would skip, if only more of it were there.
+    pub(crate) fn partial_whitespace(&self) -> bool {
+        let rest = self.rest();
+        self.syntax == Syntax::Relaxed
+            && !rest.is_empty()
+            && (rest == b"/"
+                || RELAXED_SPACES
+                    .iter()
+                    .any(|space| space.len() > rest.len() && space.starts_with(rest)))
     }
 
     fn is_identifier_start(b: u8) -> bool {
-        (b >= b'a' && b <= b'z') || (b >= b'A' && b <= b'Z') || b == b'_'
+        b.is_ascii_alphabetic() || b == b'_'
     }
 
     fn is_identifier_char(b: u8) -> bool {
         Self::is_identifier_start(b) || Self::is_digit(b)
     }
 
+    // JSON5 identifiers can also contain dollar signs.
+    fn is_relaxed_identifier_char(b: u8) -> bool {
+        Self::is_identifier_char(b) || b == b'$'
+    }
+
     fn is_digit(b: u8) -> bool {
-        b >= b'0' && b <= b'9'
+        b.is_ascii_digit()
     }
 
-    fn token(&mut self) -> Result<Token, ParseError> {
+    pub(crate) fn token(&mut self) -> Result<Token<'a>, ParseError> {
         self.skip_whitespace();
 
-        if self.eof() {
-            return Err(ParseError("Unexpected end of file".to_owned()));
-        }
+        self.token_start = self.pos;
 
-        let byte = self.peek_byte().unwrap();
+        let byte = match self.peek_byte() {
+            Some(b) => b,
+            None => return Err(self.error(ParseErrorKind::UnexpectedEof)),
+        };
 
+        let relaxed = self.syntax == Syntax::Relaxed;
         let result = match byte as char {
             '[' => {
                 self.advance();
@@ -113,81 +439,158 @@ impl<'a> Lexer<'a> {
                 Token::CloseBrace
             }
             '-' => Token::Number(self.lex_number()?),
-            d if d.is_digit(10) => Token::Number(self.lex_number()?),
-
-            '"' => {
-                // First, just find the extent of the string literal
-                self.advance();
-                let start_pos = self.pos;
-                loop {
-                    match self.peek_byte() {
-                        None => {
-                            return Err(ParseError(
-                                "Unexpected end of file while parsing string literal".to_owned(),
-                            ))
-                        }
-                        Some(b) => match b as char {
-                            '\n' => {
-                                return Err(ParseError(
-                                    "Unexpected newline while parsing string literal".to_owned(),
-                                ))
-                            }
-                            '\\' => {
-                                self.advance();
-                                if let None = self.peek_byte() {
-                                    return Err(ParseError("Unexpected end of file while parsing string literal escape sequence".to_owned()));
-                                }
-                            }
-                            '"' => break,
-                            _ => self.advance(),
-                        },
-                    }
-                }
-                let end_pos = self.pos;
-
-                let res = &self.s[start_pos..end_pos];
+            d if d.is_ascii_digit() => Token::Number(self.lex_number()?),
+            '+' | '.' if relaxed => Token::Number(self.lex_number()?),
 
-                self.advance();
-                Token::String(Self::parse_string(res)?)
-            }
+            '"' => self.lex_string(b'"')?,
+            '\'' if relaxed => self.lex_string(b'\'')?,
             _ if Self::is_identifier_start(byte) => {
-                Token::Identifier(self.take_while(Self::is_identifier_char))
+                let pred = if relaxed {
+                    Self::is_relaxed_identifier_char
+                } else {
+                    Self::is_identifier_char
+                };
+                Token::Identifier(self.take_while(pred))
+            }
+            '$' if relaxed => Token::Identifier(self.take_while(Self::is_relaxed_identifier_char)),
+            '/' if relaxed && self.rest().starts_with(b"/*") => {
+                return Err(self.error(ParseErrorKind::UnterminatedComment));
             }
             _ => {
-                return Err(ParseError(format!(
-                    "Unexpected character '{}'",
-                    byte as char
-                )));
+                let ch = self.peek_char().unwrap_or(char::REPLACEMENT_CHARACTER);
+                return Err(self.error(ParseErrorKind::UnexpectedCharacter(ch)));
             }
         };
 
+        self.token_end = self.pos;
         self.skip_whitespace();
 
         Ok(result)
     }
 
-    fn lex_number(&mut self) -> Result<f32, ParseError> {
-        let negative = if self.peek_byte() == Some(b'-') {
-            self.advance();
-            true
-        } else {
-            false
-        };
+    // Lexes a string literal that starts with the quote character at the current position.
+    fn lex_string(&mut self, quote: u8) -> Result<Token<'a>, ParseError> {
+        let relaxed = self.syntax == Syntax::Relaxed;
 
-        if self.eof() {
-            return Err(ParseError("Unexpected EOF while parsing number".to_owned()));
+        // First, just find the extent of the string literal
+        self.advance();
+        let start_pos = self.pos;
+        loop {
+            match self.peek_byte() {
+                None => return Err(self.error(ParseErrorKind::UnterminatedString)),
+                Some(b) if b == quote => break,
+                Some(b) => match b as char {
+                    '\n' => return Err(self.error(ParseErrorKind::NewlineInString)),
+                    c if c < ' ' && self.syntax == Syntax::Strict => {
+                        let kind = ParseErrorKind::ControlCharacterInString;
+                        return Err(self.error(kind));
+                    }
+                    '\\' => {
+                        self.advance();
+                        if self.peek_byte().is_none() {
+                            return Err(self.error(ParseErrorKind::UnterminatedString));
+                        }
+                        // A line continuation can end with \r\n.
+                        if relaxed && self.rest().starts_with(b"\r\n") {
+                            self.advance();
+                        }
+                        self.advance();
+                    }
+                    _ => self.advance(),
+                },
+            }
         }
+        let end_pos = self.pos;
 
+        self.advance();
+        self.parse_string(start_pos, end_pos)
+    }
+
+    // The JSON5 numbers that aren't decimal: Infinity, NaN and hex integers.  Called after the
+    // sign, if there was one.
+    fn lex_special_number(&mut self, negative: bool) -> Result<Option<Number>, ParseError> {
+        let rest = self.rest();
+        let sign = if negative { -1.0 } else { 1.0 };
+
+        for (name, f) in [(INFINITY_TOKEN, f64::INFINITY), (NAN_TOKEN, f64::NAN)] {
+            let after = rest.get(name.len()).copied();
+            if rest.starts_with(name) && !after.is_some_and(Self::is_relaxed_identifier_char) {
+                self.pos += name.len();
+                return Ok(Some(Number::from_f64(sign * f)));
+            }
+        }
+
+        if !(rest.starts_with(b"0x") || rest.starts_with(b"0X")) {
+            return Ok(None);
+        }
+        self.pos += 2;
+
+        let digits = self.take_while(|b| b.is_ascii_hexdigit());
+        if digits.is_empty() {
+            return Err(self.error(ParseErrorKind::InvalidNumber));
+        }
+
+        // Hex integers too large for a u64 become floats, like decimal ones.
+        let mut int = Some(0u64);
+        let mut float = 0.0;
+        for &b in digits {
+            let d = (b as char).to_digit(16).unwrap_or_default();
+            int = int.and_then(|n| n.checked_mul(16)?.checked_add(d as u64));
+            float = float * 16.0 + d as f64;
+        }
+
+        Ok(Some(match int {
+            Some(n) if !negative => Number::from(n),
+            Some(n) if n <= 1 << 63 => Number::from((n as i64).wrapping_neg()),
+            _ => Number::from_f64(sign * float),
+        }))
+    }
+
+    fn lex_number(&mut self) -> Result<Number, ParseError> {
         let start_offset = self.pos;
 
-        // TODO: leading zeroes are not ok.  Only one leading zero before a decimal is allowed.
+        let strict = self.syntax == Syntax::Strict;
+        let relaxed = self.syntax == Syntax::Relaxed;
+        // Whether the lexeme is valid RFC 8259.  If not, it isn't kept as the number's text, so
+        // that it can't end up in the output.
+        let mut standard = true;
 
-        self.take_while(&Self::is_digit);
+        let negative = self.peek_byte() == Some(b'-');
+        if negative {
+            self.advance();
+        } else if relaxed && self.peek_byte() == Some(b'+') {
+            self.advance();
+            standard = false;
+        }
+
+        if relaxed {
+            if let Some(n) = self.lex_special_number(negative)? {
+                return Ok(n);
+            }
+        }
+
+        let int = self.take_while(Self::is_digit);
+        if int.is_empty() {
+            if !(relaxed && self.peek_byte() == Some(b'.')) {
+                return Err(self.error(ParseErrorKind::InvalidNumber));
+            }
+            standard = false;
+        } else if int.len() > 1 && int[0] == b'0' {
+            if strict {
+                return Err(self.error_at(ParseErrorKind::InvalidNumber, start_offset));
+            }
+            standard = false;
+        }
 
         if self.peek_byte() == Some(b'.') {
             self.advance();
 
-            self.take_while(&Self::is_digit);
+            if self.take_while(Self::is_digit).is_empty() {
+                if strict || int.is_empty() {
+                    return Err(self.error(ParseErrorKind::InvalidNumber));
+                }
+                standard = false;
+            }
         }
 
         if let Some(ch) = self.peek_byte() {
@@ -199,177 +602,702 @@ impl<'a> Lexer<'a> {
                     self.advance();
                 }
 
-                self.take_while(&Self::is_digit);
+                if self.take_while(Self::is_digit).is_empty() {
+                    return Err(self.error(ParseErrorKind::InvalidNumber));
+                }
             }
         }
 
-        let end_effset = self.pos;
+        let end_offset = self.pos;
 
-        let res = std::str::from_utf8(&self.s[start_offset..end_effset])
-            .unwrap()
-            .parse::<f32>()
-            .unwrap();
+        // The lexeme is all ASCII, so this can only fail if the lexer is broken.
+        std::str::from_utf8(&self.s[start_offset..end_offset])
+            .ok()
+            .and_then(Number::from_lexeme)
+            .map(|n| if standard { n } else { n.without_text() })
+            .ok_or_else(|| self.error_at(ParseErrorKind::InvalidNumber, start_offset))
+    }
 
-        Ok(if negative { -(res as f32) } else { res as f32 })
+    // Reads the hex digits of a \u or \x escape.
+    fn hex(chars: &mut std::str::CharIndices, digits: usize) -> Option<u32> {
+        let mut n = 0;
+        for _ in 0..digits {
+            let (_, c) = chars.next()?;
+            n = n << 4 | c.to_digit(16)?;
+        }
+        Some(n)
     }
 
-    fn parse_hex_digit(d: char) -> Result<usize, ParseError> {
-        const DIGITS: &str = "01234567890ABCDEF";
-        if let Some(i) = DIGITS.find(d.to_ascii_uppercase()) {
-            Ok(i)
-        } else {
-            Err(ParseError(format!(
-                "Bad hex digit '{}' in unicode escape",
-                d
-            )))
-        }
-    }
-
-    fn parse_hex(d1: char, d2: char, d3: char, d4: char) -> Result<u32, ParseError> {
-        let a1 = Self::parse_hex_digit(d1)?;
-        let a2 = Self::parse_hex_digit(d2)?;
-        let a3 = Self::parse_hex_digit(d3)?;
-        let a4 = Self::parse_hex_digit(d4)?;
-        Ok((a1 << 24 | a2 << 16 | a3 << 8 | a4) as u32)
-    }
-
-    fn parse_string(s: &[u8]) -> Result<String, ParseError> {
-        let mut res = String::new();
-        res.reserve_exact(s.len());
-
-        let st = std::str::from_utf8(s).unwrap();
-
-        let mut chars = st.chars();
-
-        while let Some(ch) = chars.next() {
-            if ch == '\\' {
-                let n = chars.next().unwrap(); // Should be ok.  Lexer should handle this.
-                res.push(match n {
-                    '"' => '"',
-                    '\\' => '\\',
-                    '/' => '/',
-                    'b' => '\x08',
-                    'f' => '\x0c',
-                    'n' => '\n',
-                    'r' => '\r',
-                    't' => '\t',
-                    'u' => {
-                        let gch = |ch: &mut std::str::Chars| match ch.next() {
-                            None => Err(ParseError(
-                                "Unexpected EOF when parsing unicode escape in string literal"
-                                    .to_owned(),
-                            )),
-                            Some(c) => Ok(c),
-                        };
-                        let d1 = gch(&mut chars)?;
-                        let d2 = gch(&mut chars)?;
-                        let d3 = gch(&mut chars)?;
-                        let d4 = gch(&mut chars)?;
-                        char::from_u32(Self::parse_hex(d1, d2, d3, d4)?).unwrap()
+    fn hex4(chars: &mut std::str::CharIndices) -> Option<u16> {
+        Self::hex(chars, 4).map(|n| n as u16)
+    }
+
+    // Unescapes the string literal between the offsets start and end.  This is a Token::String
+    // unless lone surrogates are being preserved and there was one.
+    fn parse_string(&self, start: usize, end: usize) -> Result<Token<'a>, ParseError> {
+        let s: &'a [u8] = &self.s[start..end];
+
+        let st = std::str::from_utf8(s)
+            .map_err(|e| self.error_at(ParseErrorKind::InvalidUtf8, start + e.valid_up_to()))?;
+
+        if !s.contains(&b'\\') {
+            return Ok(Token::String(Cow::Borrowed(st)));
+        }
+
+        // Only lone surrogates preserved as WTF-8 can make this invalid UTF-8.
+        let mut res: Vec<u8> = Vec::with_capacity(s.len());
+        let mut buf = [0; 4];
+        let mut push = |res: &mut Vec<u8>, c: char| {
+            res.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        };
+
+        let mut chars = st.char_indices();
+
+        while let Some((i, ch)) = chars.next() {
+            if ch != '\\' {
+                push(&mut res, ch);
+                continue;
+            }
+
+            let bad_escape = || self.error_at(ParseErrorKind::InvalidUnicodeEscape, start + i);
+            // The lexer never ends a literal straight after a backslash.
+            let n = match chars.next() {
+                Some((_, n)) => n,
+                None => return Err(bad_escape()),
+            };
+            let c = match n {
+                '"' => '"',
+                '\\' => '\\',
+                '/' => '/',
+                'b' => '\x08',
+                'f' => '\x0c',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let unit = Self::hex4(&mut chars).ok_or_else(bad_escape)?;
+
+                    let mut pair = None;
+                    if (0xD800..0xDC00).contains(&unit) {
+                        let mut ahead = chars.clone();
+                        if let (Some((_, '\\')), Some((_, 'u'))) = (ahead.next(), ahead.next()) {
+                            match Self::hex4(&mut ahead) {
+                                Some(low) if (0xDC00..0xE000).contains(&low) => {
+                                    pair = Some(low);
+                                    chars = ahead;
+                                }
+                                _ => {}
+                            }
+                        }
                     }
-                    c => c,
-                });
-            } else {
-                res.push(ch);
+
+                    let code = match pair {
+                        Some(low) => {
+                            0x10000 + ((unit as u32 - 0xD800) << 10 | (low as u32 - 0xDC00))
+                        }
+                        None => unit as u32,
+                    };
+
+                    match char::from_u32(code) {
+                        Some(c) => c,
+                        None => match self.lone_surrogates {
+                            LoneSurrogates::Error => {
+                                let kind = ParseErrorKind::LoneSurrogate;
+                                return Err(self.error_at(kind, start + i));
+                            }
+                            LoneSurrogates::Replace => char::REPLACEMENT_CHARACTER,
+                            LoneSurrogates::Preserve => {
+                                // Encoded the way UTF-8 would encode it if it were a char.
+                                res.extend_from_slice(&[
+                                    0xE0 | (code >> 12) as u8,
+                                    0x80 | (code >> 6 & 0x3F) as u8,
+                                    0x80 | (code & 0x3F) as u8,
+                                ]);
+                                continue;
+                            }
+                        },
+                    }
+                }
+                c if self.syntax == Syntax::Strict => {
+                    let kind = ParseErrorKind::InvalidEscape(c);
+                    return Err(self.error_at(kind, start + i));
+                }
+                c if self.syntax == Syntax::Relaxed => {
+                    let invalid = || self.error_at(ParseErrorKind::InvalidEscape(c), start + i);
+                    match c {
+                        'v' => '\x0B',
+                        '0' if !chars
+                            .clone()
+                            .next()
+                            .is_some_and(|(_, d)| d.is_ascii_digit()) =>
+                        {
+                            '\0'
+                        }
+                        '0'..='9' => return Err(invalid()),
+                        'x' => Self::hex(&mut chars, 2)
+                            .and_then(char::from_u32)
+                            .ok_or_else(invalid)?,
+                        // Line continuations.
+                        '\r' => {
+                            let mut ahead = chars.clone();
+                            if let Some((_, '\n')) = ahead.next() {
+                                chars = ahead;
+                            }
+                            continue;
+                        }
+                        '\n' | '\u{2028}' | '\u{2029}' => continue,
+                        c => c,
+                    }
+                }
+                c => c,
+            };
+            push(&mut res, c);
+        }
+
+        Ok(match String::from_utf8(res) {
+            Ok(s) => Token::String(Cow::Owned(s)),
+            Err(e) => Token::RawString(e.into_bytes()),
+        })
+    }
+}
+
+// Splits a string preserved by LoneSurrogates::Preserve into runs of text and the lone surrogates
+// between them.
+pub(crate) fn wtf8_chunks(mut bytes: &[u8]) -> impl Iterator<Item = Result<&str, u16>> {
+    std::iter::from_fn(move || {
+        if bytes.is_empty() {
+            return None;
+        }
+
+        let valid = match std::str::from_utf8(bytes) {
+            Ok(_) => bytes.len(),
+            Err(e) => e.valid_up_to(),
+        };
+
+        if valid > 0 {
+            let (text, rest) = bytes.split_at(valid);
+            bytes = rest;
+            return Some(Ok(std::str::from_utf8(text).unwrap_or_default()));
+        }
+
+        // Surrogates are always three bytes.
+        let (surrogate, rest) = bytes.split_at(bytes.len().min(3));
+        bytes = rest;
+        Some(Err(match *surrogate {
+            [a, b, c] => (a as u16 & 0x0F) << 12 | (b as u16 & 0x3F) << 6 | (c as u16 & 0x3F),
+            // Not WTF-8 after all.
+            _ => 0xFFFD,
+        }))
+    })
+}
+
+// Replaces each lone surrogate with U+FFFD.
+pub(crate) fn lossy_wtf8(bytes: &[u8]) -> String {
+    wtf8_chunks(bytes)
+        .map(|chunk| chunk.unwrap_or("\u{FFFD}"))
+        .collect()
+}
+
+/// What to do with a \u escape for half of a UTF-16 surrogate pair that has no other half.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoneSurrogates {
+    /// Fail with ParseErrorKind::LoneSurrogate.
+    #[default]
+    Error,
+    /// Replace it with U+FFFD.
+    Replace,
+    /// Keep it by making the string a Value::RawString.  In object keys, which can't hold
+    /// anything but a String, it is replaced with U+FFFD.
+    Preserve,
+}
+
+/// What to do when an object has the same key more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Fail with ParseErrorKind::DuplicateKey, which gives the positions of both keys.
+    Error,
+    /// Keep the first value and ignore the rest.
+    FirstWins,
+    /// Keep the last value.  The member stays where the key first appeared.
+    #[default]
+    LastWins,
+    /// Replace the value of a key that appears more than once with an array of all its values,
+    /// in document order.  Keys that appear only once are left alone.
+    CollectAll,
+}
+
+/// How to parse a document.  The limits exist to protect against hostile input; None means no
+/// limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Defaults to Syntax::Lenient.
+    pub syntax: Syntax,
+    /// Defaults to LoneSurrogates::Error.
+    pub lone_surrogates: LoneSurrogates,
+    /// Defaults to DuplicateKeys::LastWins.
+    pub duplicate_keys: DuplicateKeys,
+    /// How many arrays and objects may be nested inside each other.  Defaults to 128.
+    ///
+    /// Parsing and dropping a Value don't recurse, so those are safe at any depth.  Everything
+    /// else that walks a whole Value does recurse, including to_string, encode,
+    /// to_string_pretty, clone and ==, and can overflow the stack on a document nested many
+    /// thousands deep.  Only raise or remove the limit if nothing but parsing and dropping will
+    /// see such documents.
+    pub max_depth: Option<usize>,
+    /// In bytes.
+    pub max_input_len: Option<usize>,
+    /// In bytes, after unescaping.  Applies to keys as well as values.
+    pub max_string_len: Option<usize>,
+    pub max_array_len: Option<usize>,
+    pub max_object_len: Option<usize>,
+    /// How many values the whole document may contain, counting every array, object and scalar.
+    pub max_nodes: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            syntax: Default::default(),
+            lone_surrogates: Default::default(),
+            duplicate_keys: Default::default(),
+            max_depth: Some(128),
+            max_input_len: None,
+            max_string_len: None,
+            max_array_len: None,
+            max_object_len: None,
+            max_nodes: None,
+        }
+    }
+}
+
+// Returns the limit if n is over it.
+pub(crate) fn exceeded(limit: Option<usize>, n: usize) -> Option<usize> {
+    limit.filter(|&limit| n > limit)
+}
+
+// Like read_until, but reads at most one byte past limit into buf, which is enough for parsing to
+// report it.  The rest, up to and including delim, is passed to skipped and discarded, so a long
+// record doesn't take more memory than a short one.  Returns how many bytes went into buf.
+pub(crate) fn read_until_limited<R: BufRead>(
+    source: &mut R,
+    delim: u8,
+    limit: Option<usize>,
+    buf: &mut Vec<u8>,
+    mut skipped: impl FnMut(&[u8]),
+) -> io::Result<usize> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return source.read_until(delim, buf),
+    };
+    let n = source
+        .by_ref()
+        .take(limit.saturating_add(1) as u64)
+        .read_until(delim, buf)?;
+    if n <= limit || buf.last() == Some(&delim) {
+        return Ok(n);
+    }
+
+    loop {
+        let available = match source.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let (len, found) = match available.iter().position(|&b| b == delim) {
+            Some(i) => (i + 1, true),
+            None => (available.len(), false),
+        };
+        if len == 0 {
+            return Ok(n);
+        }
+        skipped(&available[..len]);
+        source.consume(len);
+        if found {
+            return Ok(n);
+        }
+    }
+}
+
+// Keeps count of what ParseOptions limits as the events of a document go by.
+pub(crate) struct Limits {
+    // How many values have been started so far.
+    nodes: usize,
+    // For each open container, whether it is an array and how many elements or keys it has so
+    // far.
+    open: Vec<(bool, usize)>,
+}
+
+impl Limits {
+    pub(crate) fn new() -> Limits {
+        Limits {
+            nodes: 0,
+            open: Vec::new(),
+        }
+    }
+
+    // Checks the limits that apply when an event's token is reached.
+    pub(crate) fn event(
+        &mut self,
+        options: &ParseOptions,
+        event: &Event,
+    ) -> Result<(), ParseErrorKind> {
+        match event {
+            Event::StartArray | Event::StartObject => {
+                self.start_value(options, true)?;
+                self.open.push((matches!(event, Event::StartArray), 0));
+            }
+            Event::EndArray | Event::EndObject => {
+                self.open.pop();
+            }
+            Event::Key(k) => {
+                check_string_len(options, k.len())?;
+                if let Some((_, len)) = self.open.last_mut() {
+                    *len += 1;
+                    if let Some(limit) = exceeded(options.max_object_len, *len) {
+                        return Err(ParseErrorKind::ObjectTooLong(limit));
+                    }
+                }
+            }
+            Event::String(s) => {
+                self.start_value(options, false)?;
+                check_string_len(options, s.len())?;
+            }
+            Event::Number(_) | Event::Boolean(_) | Event::Null => {
+                self.start_value(options, false)?
             }
         }
+        Ok(())
+    }
 
-        Ok(res)
+    fn start_value(
+        &mut self,
+        options: &ParseOptions,
+        container: bool,
+    ) -> Result<(), ParseErrorKind> {
+        self.nodes += 1;
+        if let Some(limit) = exceeded(options.max_nodes, self.nodes) {
+            return Err(ParseErrorKind::TooManyNodes(limit));
+        }
+        if let Some((true, len)) = self.open.last_mut() {
+            *len += 1;
+            if let Some(limit) = exceeded(options.max_array_len, *len) {
+                return Err(ParseErrorKind::ArrayTooLong(limit));
+            }
+        }
+        match exceeded(options.max_depth, self.open.len() + 1) {
+            Some(limit) if container => Err(ParseErrorKind::DepthLimitExceeded(limit)),
+            _ => Ok(()),
+        }
     }
+}
 
-    fn rest(&self) -> &'a [u8] {
-        &self.s[self.pos..self.s.len()]
+// In bytes, after unescaping.
+pub(crate) fn check_string_len(options: &ParseOptions, len: usize) -> Result<(), ParseErrorKind> {
+    match exceeded(options.max_string_len, len) {
+        Some(limit) => Err(ParseErrorKind::StringTooLong(limit)),
+        None => Ok(()),
+    }
+}
+
+// What the parser builds: a Value, or a BorrowedValue whose strings point into the input.
+pub(crate) trait Tree<'a>: Sized {
+    type Key: Hash + Eq + Into<String>;
+
+    fn key(k: Cow<'a, str>) -> Self::Key;
+    // Null, Boolean, Number or String.
+    fn scalar(event: Event<'a>) -> Self;
+    // A string with lone surrogates in it, as WTF-8.
+    fn raw_string(bytes: Vec<u8>) -> Self;
+    fn array(a: Vec<Self>) -> Self;
+    fn object(o: Map<Self::Key, Self>) -> Self;
+    fn array_mut(&mut self) -> Option<&mut Vec<Self>>;
+}
+
+impl<'a> Tree<'a> for Value {
+    type Key = String;
+
+    fn key(k: Cow<'a, str>) -> String {
+        k.into_owned()
+    }
+
+    fn scalar(event: Event<'a>) -> Value {
+        match event {
+            Event::String(s) => Value::String(s.into_owned()),
+            Event::Number(n) => Value::Number(n),
+            Event::Boolean(b) => Value::Boolean(b),
+            _ => Value::Null,
+        }
+    }
+
+    fn raw_string(bytes: Vec<u8>) -> Value {
+        Value::RawString(bytes)
     }
+
+    fn array(a: Vec<Value>) -> Value {
+        Value::Array(a)
+    }
+
+    fn object(o: Map) -> Value {
+        Value::Object(o)
+    }
+
+    fn array_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+// Builds an object, applying the duplicate key policy.
+pub(crate) struct ObjectBuilder<K = String, V = Value> {
+    map: Map<K, V>,
+    // For each member, the offset of its first key and whether its value has already been turned
+    // into an array by DuplicateKeys::CollectAll.
+    members: Vec<(usize, bool)>,
 }
 
+impl<K: Hash + Eq, V> ObjectBuilder<K, V> {
+    pub(crate) fn new() -> ObjectBuilder<K, V> {
+        ObjectBuilder {
+            map: Map::new(),
+            members: Vec::new(),
+        }
+    }
+
+    // On a duplicate under DuplicateKeys::Error, gives back the key along with the offset where it
+    // first appeared.
+    pub(crate) fn insert<'a>(
+        &mut self,
+        key: K,
+        key_offset: usize,
+        value: V,
+        policy: DuplicateKeys,
+    ) -> Result<(), (K, usize)>
+    where
+        V: Tree<'a>,
+    {
+        let i = match self.map.index_of(&key) {
+            None => {
+                self.map.insert(key, value);
+                self.members.push((key_offset, false));
+                return Ok(());
+            }
+            Some(i) => i,
+        };
+
+        let (first_offset, collected, existing) =
+            match (self.members.get_mut(i), self.map.get_mut(&key)) {
+                (Some((first_offset, collected)), Some(existing)) => {
+                    (first_offset, collected, existing)
+                }
+                // members always has an entry for each member of map.
+                _ => return Ok(()),
+            };
+        match policy {
+            DuplicateKeys::Error => return Err((key, *first_offset)),
+            DuplicateKeys::FirstWins => {}
+            DuplicateKeys::LastWins => *existing = value,
+            DuplicateKeys::CollectAll => {
+                if !*collected {
+                    let first = std::mem::replace(existing, V::scalar(Event::Null));
+                    *existing = V::array(vec![first]);
+                    *collected = true;
+                }
+                if let Some(a) = existing.array_mut() {
+                    a.push(value);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn finish<'a>(self) -> V
+    where
+        V: Tree<'a, Key = K>,
+    {
+        V::object(self.map)
+    }
+}
+
+/// Parses a document with the default options.
+///
+/// None of the parsing functions panic, whatever the input.  Anything wrong with it is a
+/// ParseError.
 pub fn parse(s: &str) -> Result<Value, ParseError> {
-    let mut lexer = Lexer::new(s.as_bytes());
-    let v = parse_(&mut lexer)?;
+    parse_with(s, &Default::default())
+}
 
-    lexer.skip_whitespace();
+pub fn parse_with(s: &str, options: &ParseOptions) -> Result<Value, ParseError> {
+    parse_bytes_with(s.as_bytes(), options)
+}
+
+/// Like parse_with, for input that hasn't been checked to be UTF-8.  Invalid UTF-8 is an error.
+pub fn parse_bytes_with(s: &[u8], options: &ParseOptions) -> Result<Value, ParseError> {
+    parse_tree(s, options)
+}
+
+pub(crate) fn parse_tree<'a, T: Tree<'a>>(
+    s: &'a [u8],
+    options: &ParseOptions,
+) -> Result<T, ParseError> {
+    let lexer = Lexer::with_options(s, options);
+    if let Some(limit) = exceeded(options.max_input_len, s.len()) {
+        return Err(lexer.error_at(ParseErrorKind::InputTooLong(limit), limit));
+    }
 
-    if !lexer.eof() {
-        Err(ParseError(format!(
-            "Extra goop at the end of the file: {:?}",
-            lexer.rest()
-        )))
+    let (v, end) = parse_prefix(lexer, options)?;
+    if end < s.len() {
+        Err(Lexer::new(s).error_at(ParseErrorKind::TrailingCharacters, end))
     } else {
         Ok(v)
     }
 }
 
-const NULL_TOKEN: &'static [u8] = b"null";
-const TRUE_TOKEN: &'static [u8] = b"true";
-const FALSE_TOKEN: &'static [u8] = b"false";
-
-fn parse_(lexer: &mut Lexer) -> Result<Value, ParseError> {
-    let token = lexer.token()?;
-    dbg!("token '{:?}'", &token);
-
-    match token {
-        Token::Identifier(i) if i == NULL_TOKEN => Ok(Value::Null),
-        Token::Identifier(i) if i == TRUE_TOKEN => Ok(Value::Boolean(true)),
-        Token::Identifier(i) if i == FALSE_TOKEN => Ok(Value::Boolean(false)),
-        Token::String(s) => Ok(Value::String(s)),
-        Token::Number(n) => Ok(Value::Number(n)),
-        Token::OpenBracket => {
-            let mut arr = Vec::new();
-            loop {
-                let val = parse_(lexer)?;
-                arr.push(val);
-
-                let next = lexer.token()?;
-                match next {
-                    Token::CloseBracket => break,
-                    Token::Comma => continue,
-                    _ => {
-                        return Err(ParseError(format!(
-                            "Expected ',' or ']' but got '{:?}'",
-                            next
-                        )));
+// Parses the value that starts at the lexer's position.  Returns it along with the offset of the
+// first thing after it that isn't whitespace.
+pub(crate) fn parse_prefix<'a, T: Tree<'a>>(
+    lexer: Lexer<'a>,
+    options: &ParseOptions,
+) -> Result<(T, usize), ParseError> {
+    let mut parser = Parser {
+        lexer,
+        options,
+        stack: Vec::new(),
+        limits: Limits::new(),
+    };
+    let v = parser.value()?;
+
+    let lexer = &mut parser.lexer;
+    lexer.skip_whitespace();
+    Ok((v, lexer.pos))
+}
+
+pub(crate) const NULL_TOKEN: &[u8] = b"null";
+pub(crate) const TRUE_TOKEN: &[u8] = b"true";
+pub(crate) const FALSE_TOKEN: &[u8] = b"false";
+pub(crate) const INFINITY_TOKEN: &[u8] = b"Infinity";
+pub(crate) const NAN_TOKEN: &[u8] = b"NaN";
+
+// A container that is still being parsed.
+enum Partial<'a, T: Tree<'a>> {
+    Array(Vec<T>),
+    Object {
+        members: ObjectBuilder<T::Key, T>,
+        // The key of the member whose value is being parsed, and where it was.
+        key: Option<(T::Key, usize)>,
+    },
+}
+
+// Parses without recursion, so that the depth of a document is limited only by memory and
+// ParseOptions::max_depth.  The grammar is EventParser's; this builds Values out of its events and
+// enforces the limits.
+struct Parser<'a, 'o, T: Tree<'a>> {
+    lexer: Lexer<'a>,
+    options: &'o ParseOptions,
+    stack: Vec<Partial<'a, T>>,
+    limits: Limits,
+}
+
+impl<'a, 'o, T: Tree<'a>> Parser<'a, 'o, T> {
+    fn check_string_len(&self, len: usize) -> Result<(), ParseError> {
+        check_string_len(self.options, len).map_err(|kind| self.lexer.token_error(kind))
+    }
+
+    // Adds a complete value to the innermost container.  Returns it back if it is the whole
+    // document.
+    fn finish_value(&mut self, v: T) -> Result<Option<T>, ParseError> {
+        match self.stack.last_mut() {
+            None => return Ok(Some(v)),
+            Some(Partial::Array(a)) => a.push(v),
+            Some(Partial::Object { members, key }) => {
+                // EventParser always produces a key before a member's value.
+                if let Some((key, key_offset)) = key.take() {
+                    let policy = self.options.duplicate_keys;
+                    if let Err((key, first)) = members.insert(key, key_offset, v, policy) {
+                        let first = Position::of(self.lexer.s, first);
+                        let kind = ParseErrorKind::DuplicateKey {
+                            key: key.into(),
+                            first,
+                        };
+                        return Err(self.lexer.error_at(kind, key_offset));
                     }
                 }
             }
-
-            Ok(Value::Array(arr))
         }
-        Token::OpenBrace => {
-            let mut obj = HashMap::new();
 
-            loop {
-                let key = match parse_(lexer)? {
-                    Value::String(s) => s,
-                    other => {
-                        return Err(ParseError(format!(
-                            "Object keys must be strings.  Got {:?}",
-                            other
-                        )))
-                    }
-                };
+        Ok(None)
+    }
 
-                let colon = lexer.token()?;
-                if Token::Colon != colon {
-                    return Err(ParseError(format!("Expected colon but got '{:?}'", colon)));
-                }
+    fn value(&mut self) -> Result<T, ParseError> {
+        let mut events = EventParser::new(self.options.syntax);
+
+        loop {
+            let mut token = self.lexer.token()?;
 
-                let val = parse_(lexer)?;
+            // EventParser only knows about valid strings, so it is given a stand-in for this one.
+            let mut raw = None;
+            if let Token::RawString(bytes) = token {
+                raw = Some(bytes);
+                token = Token::String(Cow::Borrowed(""));
+            }
 
-                obj.insert(key, val);
+            let event = match events.token(token) {
+                Ok(Some(event)) => event,
+                Ok(None) => continue,
+                Err(kind) => return Err(self.lexer.token_error(kind)),
+            };
+            self.limits
+                .event(self.options, &event)
+                .map_err(|kind| self.lexer.token_error(kind))?;
 
-                let comma_or_brace = lexer.token()?;
-                if comma_or_brace == Token::CloseBrace {
-                    break;
-                } else if comma_or_brace != Token::Comma {
-                    return Err(ParseError(format!(
-                        "Expected comma or brace but got '{:?}'",
-                        comma_or_brace
-                    )));
+            let v = match event {
+                Event::StartArray => {
+                    self.stack.push(Partial::Array(Vec::new()));
+                    continue;
                 }
-            }
+                Event::StartObject => {
+                    self.stack.push(Partial::Object {
+                        members: ObjectBuilder::new(),
+                        key: None,
+                    });
+                    continue;
+                }
+                Event::Key(k) => {
+                    let k = match raw {
+                        Some(bytes) => {
+                            let k = lossy_wtf8(&bytes);
+                            self.check_string_len(k.len())?;
+                            Cow::Owned(k)
+                        }
+                        None => k,
+                    };
+                    let key_offset = self.lexer.token_start();
+                    if let Some(Partial::Object { key, .. }) = self.stack.last_mut() {
+                        *key = Some((T::key(k), key_offset));
+                    }
+                    continue;
+                }
+                Event::EndArray | Event::EndObject => match self.stack.pop() {
+                    Some(Partial::Array(a)) => T::array(a),
+                    Some(Partial::Object { members, .. }) => members.finish(),
+                    // EventParser never produces unbalanced events.
+                    None => continue,
+                },
+                Event::String(s) => match raw {
+                    Some(bytes) => {
+                        self.check_string_len(bytes.len())?;
+                        T::raw_string(bytes)
+                    }
+                    None => T::scalar(Event::String(s)),
+                },
+                scalar => T::scalar(scalar),
+            };
 
-            Ok(Value::Object(obj))
+            if let Some(v) = self.finish_value(v)? {
+                return Ok(v);
+            }
         }
-
-        t => Err(ParseError(format!("Unknown token '{:?}'", t))),
     }
 }
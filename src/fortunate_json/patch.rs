@@ -0,0 +1,351 @@
+use std::fmt;
+
+use crate::fortunate_json::{
+    extract_field, DecodeError, FromJSON, JsonPointer, Map, PointerError, ToJSON, Value,
+};
+
+/// One operation of a JSON Patch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchOperation {
+    /// See JsonPointer::insert.
+    Add {
+        path: JsonPointer,
+        value: Value,
+    },
+    Remove {
+        path: JsonPointer,
+    },
+    Replace {
+        path: JsonPointer,
+        value: Value,
+    },
+    /// Removes the value at from and adds it at path.
+    Move {
+        from: JsonPointer,
+        path: JsonPointer,
+    },
+    Copy {
+        from: JsonPointer,
+        path: JsonPointer,
+    },
+    /// Fails the patch unless the value at path equals value.
+    Test {
+        path: JsonPointer,
+        value: Value,
+    },
+}
+
+/// A JSON Patch (RFC 6902): a list of operations to apply to a document in order.
+///
+/// In JSON, a patch is an array of objects like `{"op": "add", "path": "/a/1", "value": 2}`.  It
+/// can be decoded from a Value with FromJSON and turned back into one with ToJSON.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch {
+    pub operations: Vec<PatchOperation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchErrorKind {
+    /// A path or from doesn't point where it needs to.
+    Pointer(PointerError),
+    /// A test operation found a different value.
+    TestFailed,
+    /// A move would put a value inside itself.
+    MoveIntoChild,
+}
+
+/// Why a patch couldn't be applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchError {
+    /// Which operation failed, starting from 0.
+    pub index: usize,
+    pub kind: PatchErrorKind,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "operation {}: ", self.index)?;
+        match &self.kind {
+            PatchErrorKind::Pointer(e) => e.fmt(f),
+            PatchErrorKind::TestFailed => f.write_str("test failed"),
+            PatchErrorKind::MoveIntoChild => f.write_str("cannot move a value into itself"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            PatchErrorKind::Pointer(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<PointerError> for PatchErrorKind {
+    fn from(e: PointerError) -> PatchErrorKind {
+        PatchErrorKind::Pointer(e)
+    }
+}
+
+fn apply_operation(doc: &mut Value, op: &PatchOperation) -> Result<(), PatchErrorKind> {
+    match op {
+        PatchOperation::Add { path, value } => path.insert(doc, value.clone())?,
+        PatchOperation::Remove { path } => {
+            path.remove(doc)?;
+        }
+        PatchOperation::Replace { path, value } => {
+            path.replace(doc, value.clone())?;
+        }
+        PatchOperation::Move { from, path } => {
+            if from == path {
+                from.get(doc)?;
+                return Ok(());
+            }
+            if path.tokens().starts_with(from.tokens()) {
+                return Err(PatchErrorKind::MoveIntoChild);
+            }
+            let v = from.remove(doc)?;
+            path.insert(doc, v)?;
+        }
+        PatchOperation::Copy { from, path } => {
+            let v = from.get(doc)?.clone();
+            path.insert(doc, v)?;
+        }
+        PatchOperation::Test { path, value } => {
+            if path.get(doc)? != value {
+                return Err(PatchErrorKind::TestFailed);
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Patch {
+    /// Applies every operation in order.  If one fails, doc is left as it was.
+    pub fn apply(&self, doc: &mut Value) -> Result<(), PatchError> {
+        let mut res = doc.clone();
+        for (index, op) in self.operations.iter().enumerate() {
+            apply_operation(&mut res, op).map_err(|kind| PatchError { index, kind })?;
+        }
+        *doc = res;
+        Ok(())
+    }
+}
+
+fn decode_operation(v: &Value) -> Result<PatchOperation, DecodeError> {
+    let o = v.as_object()?;
+
+    let mut op = String::new();
+    extract_field(o, "op", &mut op)?;
+    let mut path = JsonPointer::root();
+    extract_field(o, "path", &mut path)?;
+
+    let value = || match o.get("value") {
+        Some(v) => Ok(v.clone()),
+        None => Err(DecodeError::missing_field("value")),
+    };
+    let from = || {
+        let mut from = JsonPointer::root();
+        extract_field(o, "from", &mut from).map(|_| from)
+    };
+
+    Ok(match op.as_str() {
+        "add" => PatchOperation::Add {
+            path,
+            value: value()?,
+        },
+        "remove" => PatchOperation::Remove { path },
+        "replace" => PatchOperation::Replace {
+            path,
+            value: value()?,
+        },
+        "move" => PatchOperation::Move {
+            from: from()?,
+            path,
+        },
+        "copy" => PatchOperation::Copy {
+            from: from()?,
+            path,
+        },
+        "test" => PatchOperation::Test {
+            path,
+            value: value()?,
+        },
+        _ => return Err(DecodeError::custom(format!("unknown operation {:?}", op)).at_key("op")),
+    })
+}
+
+impl FromJSON for Patch {
+    fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
+        let a = v.as_array()?;
+        res.operations.clear();
+        for (i, op) in a.iter().enumerate() {
+            res.operations
+                .push(decode_operation(op).map_err(|e| e.at_index(i))?);
+        }
+        Ok(())
+    }
+}
+
+impl ToJSON for PatchOperation {
+    fn to_json(&self) -> Value {
+        let (op, from, path, value) = match self {
+            PatchOperation::Add { path, value } => ("add", None, path, Some(value)),
+            PatchOperation::Remove { path } => ("remove", None, path, None),
+            PatchOperation::Replace { path, value } => ("replace", None, path, Some(value)),
+            PatchOperation::Move { from, path } => ("move", Some(from), path, None),
+            PatchOperation::Copy { from, path } => ("copy", Some(from), path, None),
+            PatchOperation::Test { path, value } => ("test", None, path, Some(value)),
+        };
+
+        let mut o = Map::new();
+        o.insert("op".to_owned(), op.to_json());
+        if let Some(from) = from {
+            o.insert("from".to_owned(), from.to_json());
+        }
+        o.insert("path".to_owned(), path.to_json());
+        if let Some(value) = value {
+            o.insert("value".to_owned(), value.clone());
+        }
+        Value::Object(o)
+    }
+}
+
+impl ToJSON for Patch {
+    fn to_json(&self) -> Value {
+        self.operations.to_json()
+    }
+}
+
+// Above this many pairs of elements, arrays are compared position by position instead of looking
+// for the longest common subsequence.
+const MAX_LCS_CELLS: usize = 1 << 20;
+
+/// Makes a patch that turns a into b.
+///
+/// Objects and arrays are compared recursively so that only what changed is replaced.  Array
+/// elements that were inserted or removed are found with a longest common subsequence, so one
+/// insertion at the front doesn't replace every element after it.
+pub fn diff(a: &Value, b: &Value) -> Patch {
+    let mut patch = Patch::default();
+    diff_into(&mut JsonPointer::root(), a, b, &mut patch.operations);
+    patch
+}
+
+fn diff_into(path: &mut JsonPointer, a: &Value, b: &Value, ops: &mut Vec<PatchOperation>) {
+    if a == b {
+        return;
+    }
+
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for k in a.keys() {
+                if b.get(k).is_none() {
+                    ops.push(PatchOperation::Remove {
+                        path: child(path, k),
+                    });
+                }
+            }
+            for (k, bv) in b {
+                match a.get(k) {
+                    Some(av) => {
+                        path.push(k.clone());
+                        diff_into(path, av, bv, ops);
+                        path.pop();
+                    }
+                    None => ops.push(PatchOperation::Add {
+                        path: child(path, k),
+                        value: bv.clone(),
+                    }),
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => diff_arrays(path, a, b, ops),
+        _ => ops.push(PatchOperation::Replace {
+            path: path.clone(),
+            value: b.clone(),
+        }),
+    }
+}
+
+fn child(path: &JsonPointer, token: &str) -> JsonPointer {
+    let mut path = path.clone();
+    path.push(token);
+    path
+}
+
+fn diff_arrays(path: &mut JsonPointer, a: &[Value], b: &[Value], ops: &mut Vec<PatchOperation>) {
+    let prefix = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    let (a_rest, b_rest) = (&a[prefix..], &b[prefix..]);
+    let suffix = a_rest
+        .iter()
+        .rev()
+        .zip(b_rest.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a_mid = &a_rest[..a_rest.len() - suffix];
+    let b_mid = &b_rest[..b_rest.len() - suffix];
+
+    // Where the next element goes in the array as it is being patched.
+    let mut index = prefix;
+
+    // Elements between two kept ones: those that pair up are diffed in place, and the rest are
+    // removed or added.
+    let mut flush = |removed: &[Value], added: &[Value], index: &mut usize| {
+        for (av, bv) in removed.iter().zip(added) {
+            path.push(index.to_string());
+            diff_into(path, av, bv, ops);
+            path.pop();
+            *index += 1;
+        }
+        for _ in added.len()..removed.len() {
+            ops.push(PatchOperation::Remove {
+                path: child(path, &index.to_string()),
+            });
+        }
+        for bv in added.iter().skip(removed.len()) {
+            ops.push(PatchOperation::Add {
+                path: child(path, &index.to_string()),
+                value: bv.clone(),
+            });
+            *index += 1;
+        }
+    };
+
+    if a_mid.len().saturating_mul(b_mid.len()) > MAX_LCS_CELLS {
+        flush(a_mid, b_mid, &mut index);
+        return;
+    }
+
+    // lcs[i][j] is the length of the longest common subsequence of a_mid[i..] and b_mid[j..].
+    let width = b_mid.len() + 1;
+    let mut lcs = vec![0; (a_mid.len() + 1) * width];
+    for i in (0..a_mid.len()).rev() {
+        for j in (0..b_mid.len()).rev() {
+            lcs[i * width + j] = if a_mid[i] == b_mid[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let (mut removed_start, mut added_start) = (0, 0);
+    while i < a_mid.len() && j < b_mid.len() {
+        if a_mid[i] == b_mid[j] {
+            flush(&a_mid[removed_start..i], &b_mid[added_start..j], &mut index);
+            index += 1;
+            i += 1;
+            j += 1;
+            removed_start = i;
+            added_start = j;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    flush(&a_mid[removed_start..], &b_mid[added_start..], &mut index);
+}
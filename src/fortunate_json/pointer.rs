@@ -0,0 +1,336 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::fortunate_json::{DecodeError, FromJSON, ToJSON, Value, ValueKind};
+
+/// A JSON Pointer (RFC 6901), like `/points/3/x`, which picks out one value in a document.
+///
+/// Each reference token names an object member or, as a decimal index, an array element.  `~1`
+/// stands for `/` and `~0` for `~` within a token.  The empty pointer refers to the whole
+/// document.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct JsonPointer {
+    tokens: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerErrorKind {
+    /// The pointer doesn't start with `/`, or has a `~` that isn't followed by `0` or `1`.
+    Syntax,
+    /// There is no member with that key, or no element at that index.
+    NotFound,
+    /// An array was indexed with something other than a decimal integer without leading zeros.
+    InvalidIndex,
+    /// Tried to look inside a value that isn't an array or object.
+    NotAContainer(ValueKind),
+    /// The whole document can't be removed.
+    RemoveRoot,
+}
+
+/// Why a pointer couldn't be parsed or followed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointerError {
+    pub kind: PointerErrorKind,
+    /// The pointer up to and including the reference token that failed.
+    pub path: String,
+}
+
+impl fmt::Display for PointerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: ", self.path)?;
+        match self.kind {
+            PointerErrorKind::Syntax => f.write_str("invalid JSON pointer"),
+            PointerErrorKind::NotFound => f.write_str("not found"),
+            PointerErrorKind::InvalidIndex => f.write_str("invalid array index"),
+            PointerErrorKind::NotAContainer(kind) => write!(f, "cannot index into {}", kind),
+            PointerErrorKind::RemoveRoot => f.write_str("cannot remove the whole document"),
+        }
+    }
+}
+
+impl std::error::Error for PointerError {}
+
+fn escape(token: &str, out: &mut String) {
+    for c in token.chars() {
+        match c {
+            '~' => out.push_str("~0"),
+            '/' => out.push_str("~1"),
+            c => out.push(c),
+        }
+    }
+}
+
+// Parses an array index.  "-", which means the element after the last, is left to the caller.
+fn array_index(token: &str) -> Result<usize, PointerErrorKind> {
+    let digits = !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit());
+    if !digits || (token.len() > 1 && token.starts_with('0')) {
+        return Err(PointerErrorKind::InvalidIndex);
+    }
+    // Too big to be an index into anything.
+    token.parse().map_err(|_| PointerErrorKind::NotFound)
+}
+
+fn child<'v>(v: &'v Value, token: &str) -> Result<&'v Value, PointerErrorKind> {
+    match v {
+        Value::Object(o) => o.get(token).ok_or(PointerErrorKind::NotFound),
+        Value::Array(_) if token == "-" => Err(PointerErrorKind::NotFound),
+        Value::Array(a) => a.get(array_index(token)?).ok_or(PointerErrorKind::NotFound),
+        _ => Err(PointerErrorKind::NotAContainer(v.kind())),
+    }
+}
+
+fn child_mut<'v>(v: &'v mut Value, token: &str) -> Result<&'v mut Value, PointerErrorKind> {
+    match v {
+        Value::Object(o) => o.get_mut(token).ok_or(PointerErrorKind::NotFound),
+        Value::Array(_) if token == "-" => Err(PointerErrorKind::NotFound),
+        Value::Array(a) => {
+            let i = array_index(token)?;
+            a.get_mut(i).ok_or(PointerErrorKind::NotFound)
+        }
+        _ => Err(PointerErrorKind::NotAContainer(v.kind())),
+    }
+}
+
+impl JsonPointer {
+    /// The pointer to the whole document.
+    pub fn root() -> JsonPointer {
+        JsonPointer::default()
+    }
+
+    pub fn parse(s: &str) -> Result<JsonPointer, PointerError> {
+        if s.is_empty() {
+            return Ok(JsonPointer::root());
+        }
+
+        let syntax_error = |end: usize| PointerError {
+            kind: PointerErrorKind::Syntax,
+            path: s[..end].to_owned(),
+        };
+
+        let rest = match s.strip_prefix('/') {
+            Some(rest) => rest,
+            None => return Err(syntax_error(s.find('/').unwrap_or(s.len()))),
+        };
+
+        let mut tokens = Vec::new();
+        let mut end = 0;
+        for raw in rest.split('/') {
+            end += 1 + raw.len();
+
+            let mut token = String::with_capacity(raw.len());
+            let mut chars = raw.chars();
+            while let Some(c) = chars.next() {
+                token.push(match c {
+                    '~' => match chars.next() {
+                        Some('0') => '~',
+                        Some('1') => '/',
+                        _ => return Err(syntax_error(end)),
+                    },
+                    c => c,
+                });
+            }
+            tokens.push(token);
+        }
+
+        Ok(JsonPointer { tokens })
+    }
+
+    /// The reference tokens, unescaped.
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Adds a reference token to the end.
+    pub fn push<S: Into<String>>(&mut self, token: S) {
+        self.tokens.push(token.into());
+    }
+
+    /// Removes the last reference token.
+    pub fn pop(&mut self) -> Option<String> {
+        self.tokens.pop()
+    }
+
+    /// The pointer to the array or object that contains this one's target, and the last token.
+    /// None for the root.
+    pub fn split_last(&self) -> Option<(JsonPointer, &str)> {
+        let (last, parent) = self.tokens.split_last()?;
+        let parent = JsonPointer {
+            tokens: parent.to_vec(),
+        };
+        Some((parent, last))
+    }
+
+    // The error for the token at index i.
+    fn error(&self, i: usize, kind: PointerErrorKind) -> PointerError {
+        let mut path = String::new();
+        for token in &self.tokens[..=i.min(self.tokens.len().saturating_sub(1))] {
+            path.push('/');
+            escape(token, &mut path);
+        }
+        PointerError { kind, path }
+    }
+
+    pub fn get<'v>(&self, v: &'v Value) -> Result<&'v Value, PointerError> {
+        let mut v = v;
+        for (i, token) in self.tokens.iter().enumerate() {
+            v = child(v, token).map_err(|kind| self.error(i, kind))?;
+        }
+        Ok(v)
+    }
+
+    pub fn get_mut<'v>(&self, v: &'v mut Value) -> Result<&'v mut Value, PointerError> {
+        let mut v = v;
+        for (i, token) in self.tokens.iter().enumerate() {
+            v = child_mut(v, token).map_err(|kind| self.error(i, kind))?;
+        }
+        Ok(v)
+    }
+
+    // The parent of the target, which must exist, and the last token.  None for the root.
+    fn parent_mut<'v, 'p>(
+        &'p self,
+        v: &'v mut Value,
+    ) -> Result<Option<(&'v mut Value, &'p str)>, PointerError> {
+        let (last, parents) = match self.tokens.split_last() {
+            Some(split) => split,
+            None => return Ok(None),
+        };
+
+        let mut v = v;
+        for (i, token) in parents.iter().enumerate() {
+            v = child_mut(v, token).map_err(|kind| self.error(i, kind))?;
+        }
+        Ok(Some((v, last)))
+    }
+
+    /// Adds a value, as JSON Patch's add operation does.  An object member is added or replaced.
+    /// An array element is inserted before the one at the index, which may be one past the end or
+    /// `-` to append.  At the root, the whole document is replaced.
+    pub fn insert(&self, v: &mut Value, new: Value) -> Result<(), PointerError> {
+        let (parent, last) = match self.parent_mut(v)? {
+            Some(p) => p,
+            None => {
+                *v = new;
+                return Ok(());
+            }
+        };
+
+        let last_index = self.tokens.len() - 1;
+        match parent {
+            Value::Object(o) => {
+                o.insert(last.to_owned(), new);
+            }
+            Value::Array(a) if last == "-" => a.push(new),
+            Value::Array(a) => {
+                let i = array_index(last).map_err(|kind| self.error(last_index, kind))?;
+                if i > a.len() {
+                    return Err(self.error(last_index, PointerErrorKind::NotFound));
+                }
+                a.insert(i, new);
+            }
+            other => {
+                let kind = PointerErrorKind::NotAContainer(other.kind());
+                return Err(self.error(last_index, kind));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces a value that already exists, returning the old one.
+    pub fn replace(&self, v: &mut Value, new: Value) -> Result<Value, PointerError> {
+        Ok(std::mem::replace(self.get_mut(v)?, new))
+    }
+
+    /// Removes a value from its array or object, returning it.  Later array elements move down.
+    pub fn remove(&self, v: &mut Value) -> Result<Value, PointerError> {
+        let (parent, last) = match self.parent_mut(v)? {
+            Some(p) => p,
+            None => {
+                return Err(PointerError {
+                    kind: PointerErrorKind::RemoveRoot,
+                    path: String::new(),
+                })
+            }
+        };
+
+        let last_index = self.tokens.len() - 1;
+        let not_found = || self.error(last_index, PointerErrorKind::NotFound);
+        match parent {
+            Value::Object(o) => o.remove(last).ok_or_else(not_found),
+            Value::Array(_) if last == "-" => Err(not_found()),
+            Value::Array(a) => {
+                let i = array_index(last).map_err(|kind| self.error(last_index, kind))?;
+                if i < a.len() {
+                    Ok(a.remove(i))
+                } else {
+                    Err(not_found())
+                }
+            }
+            other => {
+                let kind = PointerErrorKind::NotAContainer(other.kind());
+                Err(self.error(last_index, kind))
+            }
+        }
+    }
+}
+
+impl FromStr for JsonPointer {
+    type Err = PointerError;
+
+    fn from_str(s: &str) -> Result<JsonPointer, PointerError> {
+        JsonPointer::parse(s)
+    }
+}
+
+impl fmt::Display for JsonPointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = String::new();
+        for token in &self.tokens {
+            s.push('/');
+            escape(token, &mut s);
+        }
+        f.write_str(&s)
+    }
+}
+
+impl Value {
+    /// Looks up a value by JSON Pointer, eg. `v.pointer("/points/3/x")`.
+    pub fn pointer(&self, pointer: &str) -> Result<&Value, PointerError> {
+        JsonPointer::parse(pointer)?.get(self)
+    }
+
+    pub fn pointer_mut(&mut self, pointer: &str) -> Result<&mut Value, PointerError> {
+        JsonPointer::parse(pointer)?.get_mut(self)
+    }
+
+    /// See JsonPointer::insert.
+    pub fn pointer_insert(&mut self, pointer: &str, v: Value) -> Result<(), PointerError> {
+        JsonPointer::parse(pointer)?.insert(self, v)
+    }
+
+    pub fn pointer_replace(&mut self, pointer: &str, v: Value) -> Result<Value, PointerError> {
+        JsonPointer::parse(pointer)?.replace(self, v)
+    }
+
+    pub fn pointer_remove(&mut self, pointer: &str) -> Result<Value, PointerError> {
+        JsonPointer::parse(pointer)?.remove(self)
+    }
+}
+
+impl FromJSON for JsonPointer {
+    fn from_json(v: &Value, res: &mut Self) -> Result<(), DecodeError> {
+        *res =
+            JsonPointer::parse(v.as_string()?).map_err(|e| DecodeError::custom(e.to_string()))?;
+        Ok(())
+    }
+}
+
+impl ToJSON for JsonPointer {
+    fn to_json(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+
+use crate::fortunate_json::serialize::{
+    write_compact, write_number, write_raw_string, write_string,
+};
+use crate::fortunate_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(usize),
+    /// One tab per level.  Tabs count as 4 columns when measuring line width.
+    Tabs,
+}
+
+impl Indent {
+    const TAB_WIDTH: usize = 4;
+
+    fn width(&self) -> usize {
+        match self {
+            Indent::Spaces(n) => *n,
+            Indent::Tabs => Self::TAB_WIDTH,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrettyConfig {
+    pub indent: Indent,
+    /// Write object members sorted by key instead of in the order the object stores them.
+    pub sort_keys: bool,
+    /// Arrays that contain only scalars are written on one line if the whole line, including
+    /// indentation and key, fits within this many columns.  None puts every element on its own
+    /// line.
+    pub inline_array_width: Option<usize>,
+    pub trailing_newline: bool,
+}
+
+impl Default for PrettyConfig {
+    fn default() -> PrettyConfig {
+        PrettyConfig {
+            indent: Indent::Spaces(2),
+            sort_keys: false,
+            inline_array_width: Some(80),
+            trailing_newline: true,
+        }
+    }
+}
+
+struct Printer<'a, W> {
+    out: &'a mut W,
+    config: &'a PrettyConfig,
+}
+
+impl<'a, W: fmt::Write> Printer<'a, W> {
+    fn newline(&mut self, depth: usize) -> fmt::Result {
+        self.out.write_char('\n')?;
+        for _ in 0..depth {
+            match self.config.indent {
+                Indent::Spaces(n) => {
+                    for _ in 0..n {
+                        self.out.write_char(' ')?;
+                    }
+                }
+                Indent::Tabs => self.out.write_char('\t')?,
+            }
+        }
+        Ok(())
+    }
+
+    // If a can go on one line, returns the text for it.
+    fn inline_array(&self, a: &[Value], column: usize) -> Option<String> {
+        let width = self.config.inline_array_width?;
+
+        if a.iter()
+            .any(|v| matches!(v, Value::Array(_) | Value::Object(_)))
+        {
+            return None;
+        }
+
+        let mut line = String::from("[");
+        for (i, elem) in a.iter().enumerate() {
+            if i > 0 {
+                line.push_str(", ");
+            }
+            // Writing to a String cannot fail.
+            let _ = write_compact(&mut line, elem);
+            // Leave room for a trailing comma.
+            if column + line.chars().count() + 2 > width {
+                return None;
+            }
+        }
+        line.push(']');
+
+        Some(line)
+    }
+
+    // column is where v starts on the current line, and is used to decide whether arrays fit.
+    fn value(&mut self, v: &Value, depth: usize, column: usize) -> fmt::Result {
+        match v {
+            Value::Null => self.out.write_str("null"),
+            Value::Boolean(true) => self.out.write_str("true"),
+            Value::Boolean(false) => self.out.write_str("false"),
+            Value::Number(n) => write_number(self.out, n),
+            Value::String(s) => write_string(self.out, s),
+            Value::RawString(b) => write_raw_string(self.out, b),
+            Value::Array(a) if a.is_empty() => self.out.write_str("[]"),
+            Value::Array(a) => {
+                if let Some(line) = self.inline_array(a, column) {
+                    return self.out.write_str(&line);
+                }
+
+                self.out.write_char('[')?;
+                for (i, elem) in a.iter().enumerate() {
+                    if i > 0 {
+                        self.out.write_char(',')?;
+                    }
+                    self.newline(depth + 1)?;
+                    self.value(elem, depth + 1, (depth + 1) * self.config.indent.width())?;
+                }
+                self.newline(depth)?;
+                self.out.write_char(']')
+            }
+            Value::Object(o) if o.is_empty() => self.out.write_str("{}"),
+            Value::Object(o) => {
+                let mut members: Vec<_> = o.iter().collect();
+                if self.config.sort_keys {
+                    members.sort_by(|a, b| a.0.cmp(b.0));
+                }
+
+                self.out.write_char('{')?;
+                for (i, (k, elem)) in members.into_iter().enumerate() {
+                    if i > 0 {
+                        self.out.write_char(',')?;
+                    }
+                    self.newline(depth + 1)?;
+
+                    let mut key = String::new();
+                    let _ = write_string(&mut key, k);
+                    self.out.write_str(&key)?;
+                    self.out.write_str(": ")?;
+
+                    let column = (depth + 1) * self.config.indent.width() + key.chars().count() + 2;
+                    self.value(elem, depth + 1, column)?;
+                }
+                self.newline(depth)?;
+                self.out.write_char('}')
+            }
+        }
+    }
+}
+
+pub fn write_pretty<W: fmt::Write>(out: &mut W, v: &Value, config: &PrettyConfig) -> fmt::Result {
+    let mut printer = Printer { out, config };
+    printer.value(v, 0, 0)?;
+
+    if config.trailing_newline {
+        printer.out.write_char('\n')?;
+    }
+
+    Ok(())
+}
+
+pub fn to_string_pretty(v: &Value, config: &PrettyConfig) -> String {
+    let mut res = String::new();
+    // Writing to a String cannot fail.
+    let _ = write_pretty(&mut res, v, config);
+    res
+}
@@ -0,0 +1,542 @@
+use std::borrow::Cow;
+use std::io;
+
+use crate::fortunate_json::parse::{
+    exceeded, lossy_wtf8, Lexer, Limits, Position, Token, FALSE_TOKEN, INFINITY_TOKEN, NAN_TOKEN,
+    NULL_TOKEN, TRUE_TOKEN,
+};
+use crate::fortunate_json::{
+    DuplicateKeys, Map, Number, ParseError, ParseErrorKind, ParseOptions, Syntax,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<'a> {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    Key(Cow<'a, str>),
+    String(Cow<'a, str>),
+    Number(Number),
+    Boolean(bool),
+    Null,
+}
+
+impl<'a> Event<'a> {
+    pub fn into_owned(self) -> Event<'static> {
+        match self {
+            Event::StartObject => Event::StartObject,
+            Event::EndObject => Event::EndObject,
+            Event::StartArray => Event::StartArray,
+            Event::EndArray => Event::EndArray,
+            Event::Key(k) => Event::Key(Cow::Owned(k.into_owned())),
+            Event::String(s) => Event::String(Cow::Owned(s.into_owned())),
+            Event::Number(n) => Event::Number(n),
+            Event::Boolean(b) => Event::Boolean(b),
+            Event::Null => Event::Null,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // Expecting any value.
+    Value,
+    // Just after '[', or after a ',' in an array with Syntax::Relaxed.  Expecting a value or ']'.
+    ArrayFirst,
+    // After an array element.  Expecting ',' or ']'.
+    ArrayNext,
+    // Just after '{', or after a ',' in an object with Syntax::Relaxed.  Expecting a key or '}'.
+    ObjectFirst,
+    // After a ',' in an object.  Expecting a key.
+    ObjectKey,
+    // After a key.  Expecting ':'.
+    ObjectColon,
+    // After a member's value.  Expecting ',' or '}'.
+    ObjectNext,
+    // The top-level value is complete.
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Array,
+    Object,
+}
+
+/// The grammar of a JSON document as a state machine that consumes one token at a time.
+pub(crate) struct EventParser {
+    stack: Vec<Container>,
+    state: State,
+    // Whether to accept the JSON5 grammar.  The lexer takes care of everything else about
+    // Syntax.
+    relaxed: bool,
+}
+
+impl EventParser {
+    pub(crate) fn new(syntax: Syntax) -> EventParser {
+        EventParser {
+            stack: Vec::new(),
+            state: State::Value,
+            relaxed: syntax == Syntax::Relaxed,
+        }
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    pub(crate) fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    fn after_value(&mut self) {
+        self.state = match self.stack.last() {
+            None => State::Done,
+            Some(Container::Array) => State::ArrayNext,
+            Some(Container::Object) => State::ObjectNext,
+        };
+    }
+
+    fn scalar<'a>(&mut self, event: Event<'a>) -> Result<Option<Event<'a>>, ParseErrorKind> {
+        self.after_value();
+        Ok(Some(event))
+    }
+
+    fn end<'a>(&mut self, event: Event<'a>) -> Result<Option<Event<'a>>, ParseErrorKind> {
+        self.stack.pop();
+        self.after_value();
+        Ok(Some(event))
+    }
+
+    fn value<'a>(&mut self, token: Token<'a>) -> Result<Option<Event<'a>>, ParseErrorKind> {
+        match token {
+            Token::Identifier(i) if i == NULL_TOKEN => self.scalar(Event::Null),
+            Token::Identifier(i) if i == TRUE_TOKEN => self.scalar(Event::Boolean(true)),
+            Token::Identifier(i) if i == FALSE_TOKEN => self.scalar(Event::Boolean(false)),
+            Token::Identifier(i) if self.relaxed && i == INFINITY_TOKEN => {
+                self.scalar(Event::Number(Number::from_f64(f64::INFINITY)))
+            }
+            Token::Identifier(i) if self.relaxed && i == NAN_TOKEN => {
+                self.scalar(Event::Number(Number::from_f64(f64::NAN)))
+            }
+            Token::Identifier(_) => Err(ParseErrorKind::UnknownIdentifier),
+            Token::String(s) => self.scalar(Event::String(s)),
+            Token::Number(n) => self.scalar(Event::Number(n)),
+            Token::OpenBracket => {
+                self.stack.push(Container::Array);
+                self.state = State::ArrayFirst;
+                Ok(Some(Event::StartArray))
+            }
+            Token::OpenBrace => {
+                self.stack.push(Container::Object);
+                self.state = State::ObjectFirst;
+                Ok(Some(Event::StartObject))
+            }
+            _ => Err(ParseErrorKind::ExpectedValue),
+        }
+    }
+
+    /// Feeds the next token.  Punctuation that doesn't correspond to an event produces None.
+    pub(crate) fn token<'a>(
+        &mut self,
+        token: Token<'a>,
+    ) -> Result<Option<Event<'a>>, ParseErrorKind> {
+        match (self.state, token) {
+            (State::Value, t) => self.value(t),
+            (State::ArrayFirst, Token::CloseBracket) => self.end(Event::EndArray),
+            (State::ArrayFirst, t) => self.value(t),
+            (State::ArrayNext, Token::Comma) => {
+                self.state = if self.relaxed {
+                    State::ArrayFirst
+                } else {
+                    State::Value
+                };
+                Ok(None)
+            }
+            (State::ArrayNext, Token::CloseBracket) => self.end(Event::EndArray),
+            (State::ArrayNext, _) => Err(ParseErrorKind::ExpectedCommaOrCloseBracket),
+            (State::ObjectFirst, Token::CloseBrace) => self.end(Event::EndObject),
+            (State::ObjectFirst, Token::String(s)) | (State::ObjectKey, Token::String(s)) => {
+                self.state = State::ObjectColon;
+                Ok(Some(Event::Key(s)))
+            }
+            (State::ObjectFirst, Token::Identifier(i))
+            | (State::ObjectKey, Token::Identifier(i))
+                if self.relaxed =>
+            {
+                self.state = State::ObjectColon;
+                // Identifiers are always ASCII.
+                let key = String::from_utf8_lossy(i);
+                Ok(Some(Event::Key(key)))
+            }
+            (State::ObjectFirst, _) | (State::ObjectKey, _) => Err(ParseErrorKind::KeyMustBeString),
+            (State::ObjectColon, Token::Colon) => {
+                self.state = State::Value;
+                Ok(None)
+            }
+            (State::ObjectColon, _) => Err(ParseErrorKind::ExpectedColon),
+            (State::ObjectNext, Token::Comma) => {
+                self.state = if self.relaxed {
+                    State::ObjectFirst
+                } else {
+                    State::ObjectKey
+                };
+                Ok(None)
+            }
+            (State::ObjectNext, Token::CloseBrace) => self.end(Event::EndObject),
+            (State::ObjectNext, _) => Err(ParseErrorKind::ExpectedCommaOrCloseBrace),
+            (State::Done, _) => Err(ParseErrorKind::TrailingCharacters),
+        }
+    }
+}
+
+/// Parses a JSON document that arrives in pieces, without needing the whole thing at once.
+///
+/// Give it input with `feed` and then take the events that input completed with `next_event`, or
+/// by iterating.  A token or UTF-8 character split between chunks is put back together.  Call
+/// `finish` once there is no more input, since some tokens, like numbers, can't be known to be
+/// complete before then.
+///
+/// Unlike most iterators, this one can produce more items after it has returned None, once it
+/// has been fed more input.  `is_done` tells whether the document is complete.  An error ends
+/// it for good: next_event keeps returning the error, while iterating yields it once and then
+/// None.
+///
+/// The limits in ParseOptions apply just as they do to parse_with, but since each member is
+/// reported as soon as it is read, DuplicateKeys only makes a difference when it is
+/// DuplicateKeys::Error.  LoneSurrogates::Preserve acts like LoneSurrogates::Replace, because
+/// events only hold valid strings.
+pub struct PushParser {
+    buf: Vec<u8>,
+    // How much of buf has been consumed.
+    pos: usize,
+    // Where buf[0] is in the input.
+    base: Position,
+    eof: bool,
+    options: ParseOptions,
+    parser: EventParser,
+    limits: Limits,
+    // The keys of each open object and where they were.  Only kept for DuplicateKeys::Error.
+    keys: Vec<Map<String, Position>>,
+    // Once there's an error, it's all there is.
+    error: Option<ParseError>,
+}
+
+enum Step {
+    Event(Event<'static>),
+    NeedInput,
+    Done,
+}
+
+impl PushParser {
+    pub fn new() -> PushParser {
+        PushParser::with_options(ParseOptions::default())
+    }
+
+    pub fn with_options(options: ParseOptions) -> PushParser {
+        PushParser {
+            buf: Vec::new(),
+            pos: 0,
+            base: Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+            eof: false,
+            parser: EventParser::new(options.syntax),
+            options,
+            limits: Limits::new(),
+            keys: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.compact();
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Says that there is no more input.
+    pub fn finish(&mut self) {
+        self.eof = true;
+    }
+
+    /// Whether the whole document has been parsed.  Anything fed after that other than
+    /// whitespace is an error.
+    pub fn is_done(&self) -> bool {
+        self.parser.is_done()
+    }
+
+    /// How many arrays and objects are currently open.
+    pub fn depth(&self) -> usize {
+        self.parser.depth()
+    }
+
+    /// Returns the next event, or None if there isn't one until more input is fed.  After finish
+    /// has been called, an incomplete document is an error.
+    pub fn next_event(&mut self) -> Result<Option<Event<'static>>, ParseError> {
+        match self.step()? {
+            Step::Event(event) => Ok(Some(event)),
+            Step::NeedInput | Step::Done => Ok(None),
+        }
+    }
+
+    // Discards what has been consumed.
+    fn compact(&mut self) {
+        self.base = self.base.advanced_by(&self.buf[..self.pos]);
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+    }
+
+    // Where an offset into buf is in the input.
+    fn position_of(&self, offset: usize) -> Position {
+        self.base.offset_by(Position::of(&self.buf, offset))
+    }
+
+    fn error_at(&self, kind: ParseErrorKind, offset: usize) -> ParseError {
+        ParseError {
+            kind,
+            position: self.position_of(offset),
+        }
+    }
+
+    fn relocate(&self, e: ParseError) -> ParseError {
+        ParseError {
+            kind: e.kind,
+            position: self.base.offset_by(e.position),
+        }
+    }
+
+    fn step(&mut self) -> Result<Step, ParseError> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+
+        let res = self.step_();
+        if let Err(e) = &res {
+            self.error = Some(e.clone());
+        }
+        res
+    }
+
+    // Applies the limits and the duplicate key policy to an event whose token started at offset.
+    fn check(&mut self, event: &Event, offset: usize) -> Result<(), ParseError> {
+        if let Err(kind) = self.limits.event(&self.options, event) {
+            return Err(self.error_at(kind, offset));
+        }
+        if self.options.duplicate_keys != DuplicateKeys::Error {
+            return Ok(());
+        }
+
+        match event {
+            Event::StartObject => self.keys.push(Map::new()),
+            Event::EndObject => {
+                self.keys.pop();
+            }
+            Event::Key(k) => {
+                let position = self.position_of(offset);
+                if let Some(keys) = self.keys.last_mut() {
+                    if let Some(&first) = keys.get(k.as_ref()) {
+                        let key = k.to_string();
+                        let kind = ParseErrorKind::DuplicateKey { key, first };
+                        return Err(self.error_at(kind, offset));
+                    }
+                    keys.insert(k.to_string(), position);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn step_(&mut self) -> Result<Step, ParseError> {
+        let len = self.base.offset + self.buf.len();
+        if let Some(limit) = exceeded(self.options.max_input_len, len) {
+            let offset = limit.saturating_sub(self.base.offset);
+            return Err(self.error_at(ParseErrorKind::InputTooLong(limit), offset));
+        }
+
+        loop {
+            let mut lexer = Lexer::with_options(&self.buf, &self.options);
+            lexer.pos = self.pos;
+
+            if self.parser.is_done() {
+                // The next chunk might finish a comment, or a slash or whitespace character that
+                // is only partly there.
+                lexer.skip_whitespace();
+                if let (Some(start), false) = (lexer.open_comment, self.eof) {
+                    self.pos = start;
+                    return Ok(Step::NeedInput);
+                }
+                self.pos = lexer.pos;
+                return if !lexer.eof() && (self.eof || !lexer.partial_whitespace()) {
+                    Err(self.error_at(ParseErrorKind::TrailingCharacters, self.pos))
+                } else if self.eof {
+                    Ok(Step::Done)
+                } else {
+                    Ok(Step::NeedInput)
+                };
+            }
+
+            // A number or identifier that runs up to the end of the buffer might continue in the
+            // next chunk, and an error there might be because the rest hasn't arrived yet.  That
+            // includes an unterminated comment, and an error within the last 3 bytes, which could
+            // be a UTF-8 character that is only partly there.  Those are only trusted once the
+            // input is finished.
+            let res = lexer.token();
+            let at_end = match &res {
+                Ok(Token::Number(_) | Token::Identifier(_)) => lexer.token_end() == self.buf.len(),
+                Ok(_) => false,
+                Err(e) => {
+                    lexer.eof()
+                        || e.kind == ParseErrorKind::UnterminatedComment
+                        || self.buf.len().saturating_sub(e.position.offset) <= 3
+                }
+            };
+            if at_end && !self.eof {
+                return Ok(Step::NeedInput);
+            }
+
+            let token = match res.map_err(|e| self.relocate(e))? {
+                Token::RawString(bytes) => Token::String(Cow::Owned(lossy_wtf8(&bytes))),
+                token => token,
+            };
+            let token_start = lexer.token_start();
+            let end = lexer.pos;
+
+            match self.parser.token(token) {
+                Ok(event) => {
+                    // A comment after the token that isn't finished yet is read again once it
+                    // is.
+                    self.pos = match lexer.open_comment {
+                        Some(start) if !self.eof => start,
+                        _ => end,
+                    };
+                    if let Some(event) = event {
+                        let event = event.into_owned();
+                        self.check(&event, token_start)?;
+                        return Ok(Step::Event(event));
+                    }
+                }
+                Err(kind) => return Err(self.error_at(kind, token_start)),
+            }
+        }
+    }
+}
+
+impl Default for PushParser {
+    fn default() -> PushParser {
+        PushParser::new()
+    }
+}
+
+impl Iterator for PushParser {
+    type Item = Result<Event<'static>, ParseError>;
+
+    // An error is only yielded once.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        self.next_event().transpose()
+    }
+}
+
+const DEFAULT_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Reads a JSON document from an io::Read as a series of events, without ever holding the whole
+/// document in memory.
+///
+/// Memory use is bounded by the chunk size, the longest single token, and the nesting depth.
+/// ParseOptions apply as they do to PushParser.
+pub struct Reader<R> {
+    source: R,
+    chunk_size: usize,
+    push: PushParser,
+    finished: bool,
+}
+
+impl<R: io::Read> Reader<R> {
+    pub fn new(source: R) -> Reader<R> {
+        Reader::with_chunk_size(source, DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(source: R, chunk_size: usize) -> Reader<R> {
+        Reader {
+            source,
+            chunk_size: chunk_size.max(1),
+            push: PushParser::new(),
+            finished: false,
+        }
+    }
+
+    pub fn with_options(source: R, options: ParseOptions) -> Reader<R> {
+        Reader {
+            push: PushParser::with_options(options),
+            ..Reader::new(source)
+        }
+    }
+
+    /// How many arrays and objects are currently open.
+    pub fn depth(&self) -> usize {
+        self.push.depth()
+    }
+
+    // Reads another chunk from the source into the push parser.
+    fn refill(&mut self) -> Result<(), ParseError> {
+        let push = &mut self.push;
+        push.compact();
+
+        let old_len = push.buf.len();
+        push.buf.resize(old_len + self.chunk_size, 0);
+
+        loop {
+            match self.source.read(&mut push.buf[old_len..]) {
+                Ok(n) => {
+                    push.buf.truncate(old_len + n);
+                    if n == 0 {
+                        push.finish();
+                    }
+                    return Ok(());
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    push.buf.truncate(old_len);
+                    return Err(push.error_at(ParseErrorKind::Io(e.kind()), old_len));
+                }
+            }
+        }
+    }
+
+    // Returns the next event, or None once the document is complete and only whitespace is left.
+    pub fn next_event(&mut self) -> Result<Option<Event<'static>>, ParseError> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let res = self.next_event_();
+        if !matches!(res, Ok(Some(_))) {
+            self.finished = true;
+        }
+        res
+    }
+
+    fn next_event_(&mut self) -> Result<Option<Event<'static>>, ParseError> {
+        loop {
+            match self.push.step()? {
+                Step::Event(event) => return Ok(Some(event)),
+                Step::Done => return Ok(None),
+                Step::NeedInput => self.refill()?,
+            }
+        }
+    }
+}
+
+impl<R: io::Read> Iterator for Reader<R> {
+    type Item = Result<Event<'static>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
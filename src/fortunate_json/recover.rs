@@ -0,0 +1,551 @@
+use std::fmt;
+
+use crate::fortunate_json::parse::{
+    exceeded, lossy_wtf8, Lexer, ObjectBuilder, Token, FALSE_TOKEN, INFINITY_TOKEN, NAN_TOKEN,
+    NULL_TOKEN, TRUE_TOKEN,
+};
+use crate::fortunate_json::{Number, ParseErrorKind, ParseOptions, Position, Syntax, Value};
+
+/// A stretch of the input, from start up to but not including end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// One of the problems found by parse_recovering.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.span.start)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Recovered {
+    /// As much of the document as could be made out.  Broken values are null.
+    pub value: Value,
+    /// In the order they were found, which is document order.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Parses a document that may have mistakes in it, carrying on past each one so that they can all
+/// be reported at once.
+///
+/// Missing commas and colons are assumed, stray tokens are skipped, a closing bracket closes
+/// whatever is open inside it, and a string missing its closing quote ends at the end of the
+/// line.  The result is the same as parse_with's for a document with no mistakes.
+///
+/// Of the limits in options, only max_depth and max_input_len are enforced.  A value nested too
+/// deeply is replaced with null.
+pub fn parse_recovering(s: &str, options: &ParseOptions) -> Recovered {
+    let mut parser = Recovering {
+        lexer: Lexer::with_options(s.as_bytes(), options),
+        options,
+        stack: Vec::new(),
+        state: State::Value,
+        root: None,
+        diagnostics: Vec::new(),
+        peeked: None,
+    };
+
+    if let Some(limit) = exceeded(options.max_input_len, s.len()) {
+        parser.diagnose(ParseErrorKind::InputTooLong(limit), limit, s.len());
+        return Recovered {
+            value: Value::Null,
+            diagnostics: parser.diagnostics,
+        };
+    }
+
+    parser.run();
+    Recovered {
+        value: parser.root.take().unwrap_or(Value::Null),
+        diagnostics: parser.diagnostics,
+    }
+}
+
+enum Tok<'a> {
+    Token(Token<'a>),
+    // Something the lexer couldn't make sense of.  It has already been reported.
+    Bad,
+    Eof,
+}
+
+// What the next token is, as far as the grammar cares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    OpenBracket,
+    CloseBracket,
+    OpenBrace,
+    CloseBrace,
+    Colon,
+    Comma,
+    String,
+    // Numbers and identifiers.
+    Other,
+    Bad,
+    Eof,
+}
+
+impl Kind {
+    fn of(tok: &Tok) -> Kind {
+        match tok {
+            Tok::Token(Token::OpenBracket) => Kind::OpenBracket,
+            Tok::Token(Token::CloseBracket) => Kind::CloseBracket,
+            Tok::Token(Token::OpenBrace) => Kind::OpenBrace,
+            Tok::Token(Token::CloseBrace) => Kind::CloseBrace,
+            Tok::Token(Token::Colon) => Kind::Colon,
+            Tok::Token(Token::Comma) => Kind::Comma,
+            Tok::Token(Token::String(_)) | Tok::Token(Token::RawString(_)) => Kind::String,
+            Tok::Token(Token::Identifier(_)) | Tok::Token(Token::Number(_)) => Kind::Other,
+            Tok::Bad => Kind::Bad,
+            Tok::Eof => Kind::Eof,
+        }
+    }
+}
+
+// The same states as EventParser's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Value,
+    ArrayFirst,
+    ArrayNext,
+    ObjectFirst,
+    ObjectKey,
+    ObjectColon,
+    ObjectNext,
+    Done,
+}
+
+enum Frame {
+    Array(Vec<Value>),
+    Object {
+        members: ObjectBuilder,
+        // The key of the member being parsed and where it starts and ends.  None if the key was
+        // unusable, in which case the member is dropped.
+        key: Option<(String, usize, usize)>,
+    },
+}
+
+struct Recovering<'a, 'o> {
+    lexer: Lexer<'a>,
+    options: &'o ParseOptions,
+    stack: Vec<Frame>,
+    state: State,
+    root: Option<Value>,
+    diagnostics: Vec<Diagnostic>,
+    // The next token and where it starts and ends, once it has been looked at.
+    peeked: Option<(Tok<'a>, usize, usize)>,
+}
+
+// Where a string literal that failed to lex should be taken to end: after its closing quote, or
+// at the end of the line if it doesn't have one.
+fn string_end(s: &[u8], start: usize) -> usize {
+    let quote = s[start];
+    let mut i = start + 1;
+    while i < s.len() {
+        match s[i] {
+            b'\n' => return i,
+            b'\\' => i += 2,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    s.len()
+}
+
+impl<'a, 'o> Recovering<'a, 'o> {
+    fn relaxed(&self) -> bool {
+        self.options.syntax == Syntax::Relaxed
+    }
+
+    fn diagnose(&mut self, kind: ParseErrorKind, start: usize, end: usize) {
+        let s = self.lexer.input();
+        self.diagnostics.push(Diagnostic {
+            kind,
+            span: Span {
+                start: Position::of(s, start),
+                end: Position::of(s, end),
+            },
+        });
+    }
+
+    // Reports a problem with the next token.
+    fn diagnose_next(&mut self, kind: ParseErrorKind) {
+        if let Some((_, start, end)) = self.peeked {
+            self.diagnose(kind, start, end);
+        }
+    }
+
+    fn lex(&mut self) -> (Tok<'a>, usize, usize) {
+        self.lexer.skip_whitespace();
+        let pos = self.lexer.pos;
+        if self.lexer.eof() {
+            return (Tok::Eof, pos, pos);
+        }
+
+        let e = match self.lexer.token() {
+            Ok(token) => {
+                let (start, end) = (self.lexer.token_start(), self.lexer.token_end());
+                return (Tok::Token(token), start, end);
+            }
+            Err(e) => e,
+        };
+
+        // Carry on from somewhere sensible: after the whole of a broken string, or else after
+        // at least one char.
+        let s = self.lexer.input();
+        let start = self.lexer.token_start();
+        let quote = s[start] == b'"' || (s[start] == b'\'' && self.relaxed());
+        let end = if e.kind == ParseErrorKind::UnterminatedComment {
+            s.len()
+        } else if quote {
+            string_end(s, start)
+        } else {
+            let mut next = start + 1;
+            while s.get(next).is_some_and(|&b| b & 0xC0 == 0x80) {
+                next += 1;
+            }
+            self.lexer.pos.max(next)
+        };
+
+        self.lexer.pos = end;
+        self.diagnose(e.kind, start, end);
+        (Tok::Bad, start, end)
+    }
+
+    fn peek(&mut self) -> Kind {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.lex());
+        }
+        match &self.peeked {
+            Some((tok, _, _)) => Kind::of(tok),
+            None => Kind::Eof,
+        }
+    }
+
+    fn bump(&mut self) -> (Tok<'a>, usize, usize) {
+        self.peek();
+        self.peeked.take().unwrap_or((Tok::Eof, 0, 0))
+    }
+
+    fn identifier(&self, i: &[u8]) -> Option<Value> {
+        Some(match i {
+            NULL_TOKEN => Value::Null,
+            TRUE_TOKEN => Value::Boolean(true),
+            FALSE_TOKEN => Value::Boolean(false),
+            INFINITY_TOKEN if self.relaxed() => Value::Number(Number::from_f64(f64::INFINITY)),
+            NAN_TOKEN if self.relaxed() => Value::Number(Number::from_f64(f64::NAN)),
+            _ => return None,
+        })
+    }
+
+    fn scalar(&mut self) -> Value {
+        match self.bump() {
+            (Tok::Token(Token::String(s)), _, _) => Value::String(s.into_owned()),
+            (Tok::Token(Token::RawString(b)), _, _) => Value::RawString(b),
+            (Tok::Token(Token::Number(n)), _, _) => Value::Number(n),
+            (Tok::Token(Token::Identifier(i)), start, end) => match self.identifier(i) {
+                Some(v) => v,
+                None => {
+                    self.diagnose(ParseErrorKind::UnknownIdentifier, start, end);
+                    Value::Null
+                }
+            },
+            _ => Value::Null,
+        }
+    }
+
+    // Reads a key.  Anything but a string is reported, but still used if it has text.
+    fn key(&mut self) -> Option<(String, usize, usize)> {
+        let (tok, start, end) = self.bump();
+        let key = match tok {
+            Tok::Token(Token::String(s)) => s.into_owned(),
+            Tok::Token(Token::RawString(b)) => lossy_wtf8(&b),
+            Tok::Token(Token::Identifier(i)) if self.relaxed() => {
+                String::from_utf8_lossy(i).into_owned()
+            }
+            Tok::Token(Token::Identifier(_)) | Tok::Token(Token::Number(_)) => {
+                self.diagnose(ParseErrorKind::KeyMustBeString, start, end);
+                let text = self.lexer.input().get(start..end).unwrap_or_default();
+                String::from_utf8_lossy(text).into_owned()
+            }
+            _ => return None,
+        };
+        Some((key, start, end))
+    }
+
+    fn insert(&mut self, members: &mut ObjectBuilder, key: (String, usize, usize), v: Value) {
+        let (key, start, end) = key;
+        let policy = self.options.duplicate_keys;
+        if let Err((key, first)) = members.insert(key, start, v, policy) {
+            let first = Position::of(self.lexer.input(), first);
+            self.diagnose(ParseErrorKind::DuplicateKey { key, first }, start, end);
+        }
+    }
+
+    // Adds a complete value to the innermost container.
+    fn finish(&mut self, v: Value) {
+        self.state = match self.stack.pop() {
+            None => {
+                self.root = Some(v);
+                State::Done
+            }
+            Some(Frame::Array(mut a)) => {
+                a.push(v);
+                self.stack.push(Frame::Array(a));
+                State::ArrayNext
+            }
+            Some(Frame::Object { mut members, key }) => {
+                if let Some(key) = key {
+                    self.insert(&mut members, key, v);
+                }
+                self.stack.push(Frame::Object { members, key: None });
+                State::ObjectNext
+            }
+        };
+    }
+
+    // Closes the innermost container.  A member still waiting for its value gets null.
+    fn close(&mut self) {
+        let v = match self.stack.pop() {
+            None => return,
+            Some(Frame::Array(a)) => Value::Array(a),
+            Some(Frame::Object { mut members, key }) => {
+                if let Some(key) = key {
+                    self.insert(&mut members, key, Value::Null);
+                }
+                members.finish()
+            }
+        };
+        self.finish(v);
+    }
+
+    fn open(&mut self, array: bool) {
+        let (_, start, end) = self.bump();
+
+        if let Some(limit) = exceeded(self.options.max_depth, self.stack.len() + 1) {
+            self.diagnose(ParseErrorKind::DepthLimitExceeded(limit), start, end);
+            // Skip the whole thing.
+            let mut depth = 1;
+            while depth > 0 {
+                match self.peek() {
+                    Kind::Eof => break,
+                    Kind::OpenBracket | Kind::OpenBrace => depth += 1,
+                    Kind::CloseBracket | Kind::CloseBrace => depth -= 1,
+                    _ => {}
+                }
+                self.bump();
+            }
+            self.finish(Value::Null);
+            return;
+        }
+
+        if array {
+            self.stack.push(Frame::Array(Vec::new()));
+            self.state = State::ArrayFirst;
+        } else {
+            self.stack.push(Frame::Object {
+                members: ObjectBuilder::new(),
+                key: None,
+            });
+            self.state = State::ObjectFirst;
+        }
+    }
+
+    // Handles a closing bracket or brace that doesn't match the innermost container.  If it
+    // matches one further out, everything inside that is closed along with it.  Otherwise it is
+    // skipped.
+    fn mismatched(&mut self, kind: Kind) {
+        let expected = match self.stack.last() {
+            Some(Frame::Array(_)) => ParseErrorKind::ExpectedCommaOrCloseBracket,
+            _ => ParseErrorKind::ExpectedCommaOrCloseBrace,
+        };
+        self.diagnose_next(expected);
+
+        let array = kind == Kind::CloseBracket;
+        let matching = self
+            .stack
+            .iter()
+            .rposition(|f| matches!(f, Frame::Array(_)) == array);
+        self.bump();
+
+        if let Some(i) = matching {
+            while self.stack.len() > i {
+                self.close();
+            }
+        }
+    }
+
+    fn top_is_array(&self) -> bool {
+        matches!(self.stack.last(), Some(Frame::Array(_)))
+    }
+
+    fn run(&mut self) {
+        loop {
+            let kind = self.peek();
+
+            if kind == Kind::Eof {
+                if self.state != State::Done {
+                    let end = self.lexer.input().len();
+                    self.diagnose(ParseErrorKind::UnexpectedEof, end, end);
+                    if self.stack.is_empty() {
+                        self.finish(Value::Null);
+                    }
+                    while !self.stack.is_empty() {
+                        self.close();
+                    }
+                }
+                return;
+            }
+
+            match (self.state, kind) {
+                (State::Done, _) => {
+                    if let Some((_, start, _)) = self.peeked {
+                        let end = self.lexer.input().len();
+                        self.diagnose(ParseErrorKind::TrailingCharacters, start, end);
+                    }
+                    return;
+                }
+
+                (State::Value | State::ArrayFirst, Kind::OpenBracket) => self.open(true),
+                (State::Value | State::ArrayFirst, Kind::OpenBrace) => self.open(false),
+                (State::Value | State::ArrayFirst, Kind::String | Kind::Other | Kind::Bad) => {
+                    let v = self.scalar();
+                    self.finish(v);
+                }
+                (State::Value | State::ArrayFirst, Kind::CloseBracket) if self.top_is_array() => {
+                    // After a comma, unless trailing commas are allowed.
+                    if self.state == State::Value && !self.relaxed() {
+                        self.diagnose_next(ParseErrorKind::ExpectedValue);
+                    }
+                    self.bump();
+                    self.close();
+                }
+                (State::Value, Kind::CloseBrace) if !self.top_is_array() => {
+                    self.diagnose_next(ParseErrorKind::ExpectedValue);
+                    if self.stack.is_empty() {
+                        self.bump();
+                    } else {
+                        self.finish(Value::Null);
+                    }
+                }
+                (State::Value | State::ArrayFirst, Kind::CloseBracket | Kind::CloseBrace) => {
+                    if self.stack.is_empty() {
+                        self.diagnose_next(ParseErrorKind::ExpectedValue);
+                        self.bump();
+                    } else {
+                        self.mismatched(kind);
+                    }
+                }
+                (State::Value | State::ArrayFirst, Kind::Comma) => {
+                    self.diagnose_next(ParseErrorKind::ExpectedValue);
+                    if self.stack.is_empty() {
+                        self.bump();
+                    } else {
+                        self.finish(Value::Null);
+                    }
+                }
+                (State::Value | State::ArrayFirst, _) => {
+                    self.diagnose_next(ParseErrorKind::ExpectedValue);
+                    self.bump();
+                }
+
+                // Already reported by the lexer.
+                (State::ArrayNext | State::ObjectColon | State::ObjectNext, Kind::Bad) => {
+                    self.bump();
+                }
+
+                (State::ArrayNext, Kind::Comma) => {
+                    self.bump();
+                    self.state = State::Value;
+                }
+                (State::ArrayNext, Kind::CloseBracket) => {
+                    self.bump();
+                    self.close();
+                }
+                (State::ArrayNext, Kind::CloseBrace) => self.mismatched(kind),
+                (State::ArrayNext, Kind::Colon) => {
+                    self.diagnose_next(ParseErrorKind::ExpectedCommaOrCloseBracket);
+                    self.bump();
+                }
+                (State::ArrayNext, _) => {
+                    // A missing comma.
+                    self.diagnose_next(ParseErrorKind::ExpectedCommaOrCloseBracket);
+                    self.state = State::Value;
+                }
+
+                (State::ObjectFirst | State::ObjectKey, Kind::CloseBrace) => {
+                    if self.state == State::ObjectKey && !self.relaxed() {
+                        self.diagnose_next(ParseErrorKind::KeyMustBeString);
+                    }
+                    self.bump();
+                    self.close();
+                }
+                (State::ObjectFirst | State::ObjectKey, Kind::String | Kind::Other | Kind::Bad) => {
+                    let key = self.key();
+                    if let Some(Frame::Object { key: k, .. }) = self.stack.last_mut() {
+                        *k = key;
+                    }
+                    self.state = State::ObjectColon;
+                }
+                (State::ObjectFirst | State::ObjectKey, Kind::CloseBracket) => {
+                    self.mismatched(kind)
+                }
+                (State::ObjectFirst | State::ObjectKey, Kind::Comma) => {
+                    self.diagnose_next(ParseErrorKind::KeyMustBeString);
+                    self.bump();
+                    self.state = State::ObjectKey;
+                }
+                (State::ObjectFirst | State::ObjectKey, Kind::Colon) => {
+                    // The value is parsed but has nowhere to go.
+                    self.diagnose_next(ParseErrorKind::KeyMustBeString);
+                    self.bump();
+                    self.state = State::Value;
+                }
+                (State::ObjectFirst | State::ObjectKey, _) => {
+                    self.diagnose_next(ParseErrorKind::KeyMustBeString);
+                    self.state = State::Value;
+                }
+
+                (State::ObjectColon, Kind::Colon) => {
+                    self.bump();
+                    self.state = State::Value;
+                }
+                (State::ObjectColon, Kind::Comma | Kind::CloseBrace | Kind::CloseBracket) => {
+                    self.diagnose_next(ParseErrorKind::ExpectedColon);
+                    self.finish(Value::Null);
+                }
+                (State::ObjectColon, _) => {
+                    // A missing colon.
+                    self.diagnose_next(ParseErrorKind::ExpectedColon);
+                    self.state = State::Value;
+                }
+
+                (State::ObjectNext, Kind::Comma) => {
+                    self.bump();
+                    self.state = State::ObjectKey;
+                }
+                (State::ObjectNext, Kind::CloseBrace) => {
+                    self.bump();
+                    self.close();
+                }
+                (State::ObjectNext, Kind::CloseBracket) => self.mismatched(kind),
+                (State::ObjectNext, Kind::Colon) => {
+                    self.diagnose_next(ParseErrorKind::ExpectedCommaOrCloseBrace);
+                    self.bump();
+                }
+                (State::ObjectNext, _) => {
+                    // A missing comma.
+                    self.diagnose_next(ParseErrorKind::ExpectedCommaOrCloseBrace);
+                    self.state = State::ObjectKey;
+                }
+            }
+        }
+    }
+}
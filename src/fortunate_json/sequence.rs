@@ -0,0 +1,242 @@
+use std::io;
+
+use crate::fortunate_json::parse::{exceeded, parse_prefix, read_until_limited, Lexer};
+use crate::fortunate_json::serialize::write_compact;
+use crate::fortunate_json::{
+    parse_bytes_with, ParseError, ParseErrorKind, ParseOptions, Position, ToJSON, Value,
+    WriterError,
+};
+
+/// Parses a series of JSON values that follow each other in one input, like `{"a":1}{"b":2} 3`,
+/// giving each along with the byte offset where it began.
+///
+/// Whitespace between values is optional except where two values would otherwise run together,
+/// as with `1 2`.  Iteration stops after the first error.  max_input_len applies to the whole
+/// input.
+pub struct Concatenated<'a> {
+    s: &'a [u8],
+    options: ParseOptions,
+    pos: usize,
+    finished: bool,
+}
+
+impl<'a> Concatenated<'a> {
+    pub fn new(s: &'a [u8]) -> Concatenated<'a> {
+        Concatenated::with_options(s, ParseOptions::default())
+    }
+
+    pub fn with_options(s: &'a [u8], options: ParseOptions) -> Concatenated<'a> {
+        Concatenated {
+            s,
+            options,
+            pos: 0,
+            finished: false,
+        }
+    }
+
+    /// Where the next value will be looked for.
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    fn next_value(&mut self) -> Result<Option<(usize, Value)>, ParseError> {
+        let mut lexer = Lexer::with_options(self.s, &self.options);
+        if let Some(limit) = exceeded(self.options.max_input_len, self.s.len()) {
+            return Err(lexer.error_at(ParseErrorKind::InputTooLong(limit), limit));
+        }
+
+        lexer.pos = self.pos;
+        lexer.skip_whitespace();
+        if lexer.eof() {
+            return Ok(None);
+        }
+
+        let start = lexer.pos;
+        let (v, end) = parse_prefix(lexer, &self.options)?;
+        self.pos = end;
+        Ok(Some((start, v)))
+    }
+}
+
+impl<'a> Iterator for Concatenated<'a> {
+    type Item = Result<(usize, Value), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let res = self.next_value();
+        if !matches!(res, Ok(Some(_))) {
+            self.finished = true;
+        }
+        res.transpose()
+    }
+}
+
+const RECORD_SEPARATOR: u8 = 0x1E;
+
+/// Reads an RFC 7464 JSON text sequence, where each value is preceded by an ASCII record
+/// separator (0x1E) and followed by a newline.
+///
+/// A record that fails to parse is returned as an error and reading carries on with the next one,
+/// so that one truncated write doesn't lose the rest of the sequence.  As the RFC requires, a
+/// number, true, false or null that isn't followed by whitespace is reported as
+/// ParseErrorKind::TruncatedRecord, since it may have been cut short.  Empty records are skipped.
+pub struct JsonSeqReader<R> {
+    source: R,
+    options: ParseOptions,
+    buf: Vec<u8>,
+    // Where buf starts in the stream.
+    base: Position,
+    // Whether the first record separator has been read.
+    started: bool,
+    finished: bool,
+}
+
+impl<R: io::BufRead> JsonSeqReader<R> {
+    pub fn new(source: R) -> JsonSeqReader<R> {
+        JsonSeqReader::with_options(source, ParseOptions::default())
+    }
+
+    /// Parses each record with options.  max_input_len applies to each record separately.
+    pub fn with_options(source: R, options: ParseOptions) -> JsonSeqReader<R> {
+        JsonSeqReader {
+            source,
+            options,
+            buf: Vec::new(),
+            base: Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+            started: false,
+            finished: false,
+        }
+    }
+
+    fn next_record(&mut self) -> Option<Result<Value, ParseError>> {
+        loop {
+            self.buf.clear();
+            // How far what was too long to keep goes past buf.
+            let mut skipped = Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            };
+            let read = read_until_limited(
+                &mut self.source,
+                RECORD_SEPARATOR,
+                self.options.max_input_len,
+                &mut self.buf,
+                |s| skipped = skipped.advanced_by(s),
+            );
+            let n = match read {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(ParseError {
+                        kind: ParseErrorKind::Io(e.kind()),
+                        position: self.base,
+                    }));
+                }
+            };
+            if n == 0 {
+                self.finished = true;
+                return None;
+            }
+
+            let start = self.base;
+            self.base = self.base.advanced_by(&self.buf).offset_by(skipped);
+
+            let record = match self.buf.split_last() {
+                Some((&RECORD_SEPARATOR, record)) => record,
+                _ => &self.buf[..],
+            };
+            if record.iter().all(u8::is_ascii_whitespace) {
+                self.started = true;
+                continue;
+            }
+
+            // The separator that began this record is the last byte of the previous read.
+            let relocate = |e: ParseError| ParseError {
+                kind: e.kind,
+                position: start.offset_by(e.position),
+            };
+
+            if !self.started {
+                self.started = true;
+                let kind = ParseErrorKind::MissingRecordSeparator;
+                return Some(Err(relocate(Lexer::new(record).error_at(kind, 0))));
+            }
+
+            let v = match parse_bytes_with(record, &self.options) {
+                Ok(v) => v,
+                Err(e) => return Some(Err(relocate(e))),
+            };
+
+            let scalar = matches!(v, Value::Number(_) | Value::Boolean(_) | Value::Null);
+            if scalar && !record.last().is_some_and(u8::is_ascii_whitespace) {
+                let kind = ParseErrorKind::TruncatedRecord;
+                let e = Lexer::new(record).error_at(kind, record.len());
+                return Some(Err(relocate(e)));
+            }
+
+            return Some(Ok(v));
+        }
+    }
+}
+
+impl<R: io::BufRead> Iterator for JsonSeqReader<R> {
+    type Item = Result<Value, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        self.next_record()
+    }
+}
+
+/// Writes an RFC 7464 JSON text sequence: each value compact, between a record separator and a
+/// newline.
+pub struct JsonSeqWriter<W> {
+    out: W,
+    record: String,
+}
+
+impl<W: io::Write> JsonSeqWriter<W> {
+    pub fn new(out: W) -> JsonSeqWriter<W> {
+        JsonSeqWriter {
+            out,
+            record: String::new(),
+        }
+    }
+
+    pub fn value<T>(&mut self, v: &T) -> Result<(), WriterError>
+    where
+        T: ToJSON + ?Sized,
+    {
+        self.write_value(&v.to_json())
+    }
+
+    /// Like value, but avoids converting a Value that already exists.
+    pub fn write_value(&mut self, v: &Value) -> Result<(), WriterError> {
+        self.record.clear();
+        self.record.push(RECORD_SEPARATOR as char);
+        write_compact(&mut self.record, v).map_err(WriterError::Fmt)?;
+        self.record.push('\n');
+        self.out
+            .write_all(self.record.as_bytes())
+            .map_err(WriterError::Io)
+    }
+
+    pub fn flush(&mut self) -> Result<(), WriterError> {
+        self.out.flush().map_err(WriterError::Io)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
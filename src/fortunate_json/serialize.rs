@@ -0,0 +1,102 @@
+use std::fmt;
+
+use crate::fortunate_json::parse::wtf8_chunks;
+use crate::fortunate_json::{Number, Value};
+
+// Everything that writes JSON text goes through these functions so that strings and numbers
+// come out the same way no matter which writer produced them.
+
+pub(crate) fn write_string<W: fmt::Write>(out: &mut W, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    write_escaped(out, s)?;
+    out.write_char('"')
+}
+
+// Writes a Value::RawString, escaping its lone surrogates so that it parses back the same.
+pub(crate) fn write_raw_string<W: fmt::Write>(out: &mut W, bytes: &[u8]) -> fmt::Result {
+    out.write_char('"')?;
+    for chunk in wtf8_chunks(bytes) {
+        match chunk {
+            Ok(s) => write_escaped(out, s)?,
+            Err(unit) => write!(out, "\\u{:04x}", unit)?,
+        }
+    }
+    out.write_char('"')
+}
+
+fn write_escaped<W: fmt::Write>(out: &mut W, s: &str) -> fmt::Result {
+    let mut start = 0;
+    for (i, ch) in s.char_indices() {
+        let escape = match ch {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            '\x08' => "\\b",
+            '\x0c' => "\\f",
+            c if (c as u32) < 0x20 => "",
+            _ => continue,
+        };
+
+        out.write_str(&s[start..i])?;
+        if escape.is_empty() {
+            write!(out, "\\u{:04x}", ch as u32)?;
+        } else {
+            out.write_str(escape)?;
+        }
+        start = i + ch.len_utf8();
+    }
+    out.write_str(&s[start..])
+}
+
+pub(crate) fn write_number<W: fmt::Write>(out: &mut W, n: &Number) -> fmt::Result {
+    write!(out, "{}", n)
+}
+
+/// Writes v as compact JSON with no whitespace.
+pub fn write_compact<W: fmt::Write>(out: &mut W, v: &Value) -> fmt::Result {
+    match v {
+        Value::Null => out.write_str("null"),
+        Value::Boolean(true) => out.write_str("true"),
+        Value::Boolean(false) => out.write_str("false"),
+        Value::Number(n) => write_number(out, n),
+        Value::String(s) => write_string(out, s),
+        Value::RawString(b) => write_raw_string(out, b),
+        Value::Array(a) => {
+            out.write_char('[')?;
+            for (i, elem) in a.iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                write_compact(out, elem)?;
+            }
+            out.write_char(']')
+        }
+        Value::Object(o) => {
+            out.write_char('{')?;
+            for (i, (k, elem)) in o.iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                write_string(out, k)?;
+                out.write_char(':')?;
+                write_compact(out, elem)?;
+            }
+            out.write_char('}')
+        }
+    }
+}
+
+pub fn to_string(v: &Value) -> String {
+    let mut res = String::new();
+    // Writing to a String cannot fail.
+    let _ = write_compact(&mut res, v);
+    res
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_compact(f, self)
+    }
+}
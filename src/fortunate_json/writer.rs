@@ -0,0 +1,254 @@
+use std::fmt;
+use std::io;
+
+use crate::fortunate_json::serialize::{write_compact, write_string};
+use crate::fortunate_json::{ToJSON, Value};
+
+#[derive(Debug)]
+pub enum WriterError {
+    Io(io::Error),
+    Fmt(fmt::Error),
+    /// A value was written inside an object without a key before it.
+    MissingKey,
+    /// An object was closed straight after a key.
+    MissingValue,
+    /// A key was written outside of an object, or twice in a row.
+    UnexpectedKey,
+    /// end_object or end_array did not match the innermost open container.
+    MismatchedEnd,
+    /// A second value was written at the top level.
+    MultipleRoots,
+    /// finish was called with containers still open, a key without a value, or nothing at all.
+    Incomplete,
+}
+
+impl fmt::Display for WriterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriterError::Io(e) => write!(f, "I/O error while writing JSON: {}", e),
+            WriterError::Fmt(_) => write!(f, "Formatter error while writing JSON"),
+            WriterError::MissingKey => write!(f, "Object members must have a key"),
+            WriterError::MissingValue => write!(f, "Object members must have a value"),
+            WriterError::UnexpectedKey => write!(f, "Key written outside of an object member"),
+            WriterError::MismatchedEnd => write!(f, "Mismatched end of array or object"),
+            WriterError::MultipleRoots => write!(f, "More than one top-level value"),
+            WriterError::Incomplete => write!(f, "Document is incomplete"),
+        }
+    }
+}
+
+impl std::error::Error for WriterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WriterError::Io(e) => Some(e),
+            WriterError::Fmt(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Somewhere a JsonWriter can send its output.
+pub trait Output {
+    type Inner;
+
+    fn write_str(&mut self, s: &str) -> Result<(), WriterError>;
+    fn into_inner(self) -> Self::Inner;
+}
+
+pub struct FmtOutput<W>(W);
+
+impl<W: fmt::Write> Output for FmtOutput<W> {
+    type Inner = W;
+
+    fn write_str(&mut self, s: &str) -> Result<(), WriterError> {
+        self.0.write_str(s).map_err(WriterError::Fmt)
+    }
+
+    fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+pub struct IoOutput<W>(W);
+
+impl<W: io::Write> Output for IoOutput<W> {
+    type Inner = W;
+
+    fn write_str(&mut self, s: &str) -> Result<(), WriterError> {
+        self.0.write_all(s.as_bytes()).map_err(WriterError::Io)
+    }
+
+    fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+// Lets the shared serialization functions write to an Output while keeping hold of the real error.
+struct Adapter<'a, O> {
+    out: &'a mut O,
+    error: Option<WriterError>,
+}
+
+impl<'a, O: Output> fmt::Write for Adapter<'a, O> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.out.write_str(s).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+enum Frame {
+    Array { len: usize },
+    Object { len: usize, has_key: bool },
+}
+
+/// Writes a JSON document one piece at a time without building a Value for the whole thing.
+///
+/// Output is compact and is formatted exactly as `to_string` would format the same document.
+pub struct JsonWriter<O> {
+    out: O,
+    stack: Vec<Frame>,
+    root_written: bool,
+}
+
+impl<W: fmt::Write> JsonWriter<FmtOutput<W>> {
+    pub fn new(out: W) -> JsonWriter<FmtOutput<W>> {
+        JsonWriter::with_output(FmtOutput(out))
+    }
+}
+
+impl<W: io::Write> JsonWriter<IoOutput<W>> {
+    pub fn from_io(out: W) -> JsonWriter<IoOutput<W>> {
+        JsonWriter::with_output(IoOutput(out))
+    }
+}
+
+impl<O: Output> JsonWriter<O> {
+    pub fn with_output(out: O) -> JsonWriter<O> {
+        JsonWriter {
+            out,
+            stack: Vec::new(),
+            root_written: false,
+        }
+    }
+
+    fn write<F>(&mut self, f: F) -> Result<(), WriterError>
+    where
+        F: FnOnce(&mut Adapter<O>) -> fmt::Result,
+    {
+        let mut adapter = Adapter {
+            out: &mut self.out,
+            error: None,
+        };
+
+        match f(&mut adapter) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(adapter.error.unwrap_or(WriterError::Fmt(e))),
+        }
+    }
+
+    // Checks that a value may be written here and writes the separator that goes before it.
+    fn before_value(&mut self) -> Result<(), WriterError> {
+        let separator = match self.stack.last_mut() {
+            None if self.root_written => return Err(WriterError::MultipleRoots),
+            None => {
+                self.root_written = true;
+                ""
+            }
+            Some(Frame::Array { len }) => {
+                *len += 1;
+                if *len > 1 {
+                    ","
+                } else {
+                    ""
+                }
+            }
+            Some(Frame::Object { has_key, .. }) => {
+                if !*has_key {
+                    return Err(WriterError::MissingKey);
+                }
+                *has_key = false;
+                ""
+            }
+        };
+
+        self.out.write_str(separator)
+    }
+
+    pub fn key(&mut self, key: &str) -> Result<(), WriterError> {
+        let first = match self.stack.last_mut() {
+            Some(Frame::Object { len, has_key }) if !*has_key => {
+                *has_key = true;
+                *len += 1;
+                *len == 1
+            }
+            _ => return Err(WriterError::UnexpectedKey),
+        };
+
+        if !first {
+            self.out.write_str(",")?;
+        }
+
+        self.write(|out| write_string(out, key))?;
+        self.out.write_str(":")
+    }
+
+    pub fn value<T>(&mut self, v: &T) -> Result<(), WriterError>
+    where
+        T: ToJSON + ?Sized,
+    {
+        self.write_value(&v.to_json())
+    }
+
+    /// Like value, but avoids converting a Value that already exists.
+    pub fn write_value(&mut self, v: &Value) -> Result<(), WriterError> {
+        self.before_value()?;
+        self.write(|out| write_compact(out, v))
+    }
+
+    pub fn begin_array(&mut self) -> Result<(), WriterError> {
+        self.before_value()?;
+        self.stack.push(Frame::Array { len: 0 });
+        self.out.write_str("[")
+    }
+
+    pub fn end_array(&mut self) -> Result<(), WriterError> {
+        match self.stack.last() {
+            Some(Frame::Array { .. }) => {
+                self.stack.pop();
+                self.out.write_str("]")
+            }
+            _ => Err(WriterError::MismatchedEnd),
+        }
+    }
+
+    pub fn begin_object(&mut self) -> Result<(), WriterError> {
+        self.before_value()?;
+        self.stack.push(Frame::Object {
+            len: 0,
+            has_key: false,
+        });
+        self.out.write_str("{")
+    }
+
+    pub fn end_object(&mut self) -> Result<(), WriterError> {
+        match self.stack.last() {
+            Some(Frame::Object { has_key: false, .. }) => {
+                self.stack.pop();
+                self.out.write_str("}")
+            }
+            Some(Frame::Object { has_key: true, .. }) => Err(WriterError::MissingValue),
+            _ => Err(WriterError::MismatchedEnd),
+        }
+    }
+
+    /// Checks that the document is complete and returns the underlying writer.
+    pub fn finish(self) -> Result<O::Inner, WriterError> {
+        if !self.root_written || !self.stack.is_empty() {
+            Err(WriterError::Incomplete)
+        } else {
+            Ok(self.out.into_inner())
+        }
+    }
+}
@@ -1,7 +1,19 @@
 use crate::fortunate_json::{
-    decode, extract_field, parse, DecodeError, FromJSON, JSONError, Value,
+    decode, decode_borrowed, decode_with, diff, encode, extract_borrowed_field, extract_field,
+    merge_all, merge_diff, parse, parse_borrowed, parse_borrowed_with, parse_bytes_with,
+    parse_recovering, parse_with, to_string_pretty, ArrayMerge, BorrowedValue, Concatenated,
+    DecodeError, DuplicateKeys, Event, FromBorrowedJSON, FromJSON, Indent, JSONError, JsonPath,
+    JsonPointer, JsonSeqReader, JsonSeqWriter, JsonWriter, LoneSurrogates, Map, MergeOptions,
+    NdjsonReader, NdjsonWriter, Number, ParseError, ParseErrorKind, ParseOptions, Patch,
+    PatchError, PatchErrorKind, PathSegment, PointerErrorKind, Position, PrettyConfig, PushParser,
+    Reader, Syntax, ToJSON, Value, ValueKind, WriterError,
 };
+use std::borrow::Cow;
 use std::collections::hash_map::HashMap;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+
+mod json_test_suite;
 
 // TODO: Like a billion tests around error conditions.
 
@@ -45,13 +57,54 @@ fn string() {
 #[test]
 fn busted_unicode_escape() {
     assert_eq!(
-        Err(JSONError::ParseError(
-            "Unexpected EOF when parsing unicode escape in string literal".to_owned()
-        )),
+        Err(JSONError::ParseError(ParseError {
+            kind: ParseErrorKind::InvalidUnicodeEscape,
+            position: Position {
+                offset: 1,
+                line: 1,
+                column: 2
+            }
+        })),
         decode::<String>("\"\\u00\"")
     );
 }
 
+#[test]
+fn escaped_quote() {
+    let expected = Value::String("say \"hi\"".to_owned());
+
+    assert_eq!(Ok(expected), parse("\"say \\\"hi\\\"\""));
+}
+
+#[test]
+fn error_positions() {
+    let err = parse("{\n  \"a\": [1, 2,\n  \"ü\" 3]\n}").unwrap_err();
+    assert_eq!(ParseErrorKind::ExpectedCommaOrCloseBracket, err.kind);
+    assert_eq!(
+        Position {
+            offset: 23,
+            line: 3,
+            column: 7
+        },
+        err.position
+    );
+    assert_eq!("Expected ',' or ']' at line 3, column 7", err.to_string());
+}
+
+#[test]
+fn error_kinds() {
+    let kind = |s| parse(s).unwrap_err().kind;
+
+    assert_eq!(ParseErrorKind::UnexpectedEof, kind("[1,"));
+    assert_eq!(ParseErrorKind::UnexpectedCharacter('@'), kind("[@]"));
+    assert_eq!(ParseErrorKind::UnterminatedString, kind("\"abc"));
+    assert_eq!(ParseErrorKind::UnknownIdentifier, kind("nul"));
+    assert_eq!(ParseErrorKind::ExpectedColon, kind("{\"a\" 1}"));
+    assert_eq!(ParseErrorKind::KeyMustBeString, kind("{1: 1}"));
+    assert_eq!(ParseErrorKind::ExpectedValue, kind("[:]"));
+    assert_eq!(ParseErrorKind::TrailingCharacters, kind("[1] x"));
+}
+
 #[test]
 fn japanese() {
     let expected = Value::String("こんにちは".to_owned());
@@ -68,7 +121,7 @@ fn string_with_newline() {
 
 #[test]
 fn object() {
-    let expected = Value::Object(HashMap::from([
+    let expected = Value::Object(Map::from([
         ("foo".to_owned(), Value::String("bar".to_owned())),
         ("baz".to_owned(), Value::Boolean(true)),
     ]));
@@ -79,32 +132,110 @@ fn object() {
 #[test]
 fn integers() {
     let expected = Value::Array(vec![
-        Value::Number(0.0),
-        Value::Number(2.0),
-        Value::Number(4.0),
-        Value::Number(8.0),
-        Value::Number(128.0),
-        Value::Number(65535.0),
-        Value::Number(-131085.0),
+        Value::Number(Number::from(0)),
+        Value::Number(Number::from(2)),
+        Value::Number(Number::from(4)),
+        Value::Number(Number::from(8)),
+        Value::Number(Number::from(128)),
+        Value::Number(Number::from(65535)),
+        Value::Number(Number::from(-131085)),
     ]);
 
     assert_eq!(Ok(expected), parse("[0, 2, 4 , 8, 128 \t ,65535, -131085]"));
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn float() {
-    let expected = Value::Number(3.141);
+    let expected = Value::Number(Number::from_f64(3.141));
 
     assert_eq!(Ok(expected), parse("3.141"));
 }
 
 #[test]
 fn exponential_notation() {
-    let expected = Value::Array(vec![Value::Number(1000.0), Value::Number(0.00055)]);
+    let expected = Value::Array(vec![
+        Value::Number(Number::from_f64(1000.0)),
+        Value::Number(Number::from_f64(0.00055)),
+    ]);
 
     assert_eq!(Ok(expected), parse("[1e3, 5.5e-4]"));
 }
 
+#[test]
+fn large_integers_are_exact() {
+    let v = parse("[9007199254740993, -9223372036854775808, 18446744073709551615]").unwrap();
+    let a = v.as_array().unwrap();
+
+    assert_eq!(Ok(9007199254740993), a[0].as_u64());
+    assert_eq!(Ok(i64::MIN), a[1].as_i64());
+    assert_eq!(Ok(u64::MAX), a[2].as_u64());
+}
+
+#[test]
+fn lossy_conversions_are_errors() {
+    let v = parse("[9007199254740993, 16777217, -1, 1.5]").unwrap();
+    let a = v.as_array().unwrap();
+
+    assert!(a[0].as_f64().is_err());
+    assert!(a[1].as_float().is_err());
+    assert!(a[2].as_u64().is_err());
+    assert!(a[3].as_i64().is_err());
+
+    assert!(matches!(
+        decode::<u32>("4294967296"),
+        Err(JSONError::DecodeError(_))
+    ));
+    assert!(matches!(
+        decode::<u32>("2.5"),
+        Err(JSONError::DecodeError(_))
+    ));
+    assert_eq!(Ok(3), decode::<u32>("3.0"));
+
+    // A float converts to an integer only if nothing is lost, going by the text it was written
+    // as when there is one.
+    assert_eq!(
+        Ok(9007199254740993),
+        parse("9007199254740993.0").unwrap().as_i64()
+    );
+    assert_eq!(
+        Ok(12345678901234567891),
+        decode::<u64>("12345678901234567891.0")
+    );
+    assert_eq!(Ok(1500), decode::<u64>("1.5e3"));
+    assert!(decode::<u64>("18446744073709551616.0").is_err());
+    assert!(decode::<i64>("0.99999999999999999999").is_err());
+    assert_eq!(Some(1 << 53), Number::from_f64(9007199254740992.0).as_u64());
+    assert_eq!(None, Number::from_f64(18014398509481984.0).as_u64());
+
+    // Integers too large for a u64 can't be an f64 exactly either.
+    let big = parse("[18446744073709551617, -9223372036854775809]").unwrap();
+    assert!(big[0].as_f64().is_err());
+    assert!(big[1].as_f64().is_err());
+    assert_eq!(18446744073709551617.0, big[0].as_number().unwrap().to_f64());
+    assert_eq!(Ok(big[0].clone()), parse("1.8446744073709552e19"));
+}
+
+#[test]
+fn number_keeps_original_text() {
+    let v = parse("[1.50, 1e3, -0]").unwrap();
+    let a = v.as_array().unwrap();
+
+    let texts: Vec<_> = a
+        .iter()
+        .map(|n| n.as_number().unwrap().as_str().unwrap())
+        .collect();
+    assert_eq!(vec!["1.50", "1e3", "-0"], texts);
+    assert_eq!(Value::Number(Number::from(1000)), a[1]);
+}
+
+#[test]
+fn malformed_numbers() {
+    assert!(parse("-").is_err());
+    assert!(parse("1e").is_err());
+    assert!(parse("[1e+]").is_err());
+}
+
 #[derive(Debug, PartialEq, Default)]
 struct Point {
     x: f32,
@@ -122,7 +253,17 @@ impl FromJSON for Point {
     }
 }
 
+impl ToJSON for Point {
+    fn to_json(&self) -> Value {
+        Value::Object(Map::from([
+            ("x".to_owned(), self.x.to_json()),
+            ("y".to_owned(), self.y.to_json()),
+        ]))
+    }
+}
+
 #[test]
+#[allow(clippy::approx_constant)]
 fn unpack_struct() {
     let mut p = Point { x: 0.0, y: 0.0 };
 
@@ -151,6 +292,15 @@ impl FromJSON for Mesh {
     }
 }
 
+impl ToJSON for Mesh {
+    fn to_json(&self) -> Value {
+        Value::Object(Map::from([
+            ("points".to_owned(), self.points.to_json()),
+            ("indeces".to_owned(), self.indeces.to_json()),
+        ]))
+    }
+}
+
 #[test]
 fn unpack_vec() {
     let expected = Mesh {
@@ -175,3 +325,2601 @@ fn unpack_vec() {
 
 #[test]
 fn unpack_map() {}
+
+#[test]
+fn decode_error_path() {
+    let json = "{\"points\":[{\"x\":0, \"y\":0}, {\"x\":1, \"y\":1}, {\"x\":2, \"y\":\"two\"}], \"indeces\":[0]}";
+
+    let err = match decode::<Mesh>(json) {
+        Err(JSONError::DecodeError(e)) => e,
+        other => panic!("Expected a decode error, got {:?}", other),
+    };
+
+    assert_eq!(
+        vec![
+            PathSegment::Key("points".to_owned()),
+            PathSegment::Index(2),
+            PathSegment::Key("y".to_owned())
+        ],
+        err.path
+    );
+    assert_eq!(Some("f32"), err.expected);
+    assert_eq!(Some(ValueKind::String), err.actual);
+    assert_eq!("$.points[2].y: expected f32, got string", err.to_string());
+}
+
+#[test]
+fn decode_error_missing_field() {
+    let err = match decode::<Mesh>("{\"points\":[{\"x\":0}], \"indeces\":[0]}") {
+        Err(JSONError::DecodeError(e)) => e,
+        other => panic!("Expected a decode error, got {:?}", other),
+    };
+
+    assert_eq!("$.points[0].y: missing field", err.to_string());
+}
+
+#[test]
+fn decode_error_map_key() {
+    let err = match decode::<HashMap<String, u32>>("{\"a b\": 1, \"c\": -2}") {
+        Err(JSONError::DecodeError(e)) => e,
+        other => panic!("Expected a decode error, got {:?}", other),
+    };
+
+    assert_eq!(
+        "$.c: expected u32, got number: number cannot be represented exactly",
+        err.to_string()
+    );
+
+    let mut err = DecodeError::custom("bad").at_key("a b").at_index(3);
+    assert_eq!("$[3][\"a b\"]: bad", err.to_string());
+    err.message = None;
+    assert_eq!("$[3][\"a b\"]: invalid value", err.to_string());
+}
+
+#[test]
+fn encode_prims() {
+    assert_eq!("null", encode(&Value::Null));
+    assert_eq!(
+        "[true,false]",
+        encode(&vec![Value::Boolean(true), Value::Boolean(false)])
+    );
+    assert_eq!(
+        "[1,-2,3.5,0.1]",
+        encode(&vec![
+            Value::Number(Number::from(1)),
+            Value::Number(Number::from(-2)),
+            Value::Number(Number::from_f64(3.5)),
+            0.1f32.to_json(),
+        ])
+    );
+    assert_eq!("[null,7]", encode(&vec![None, Some(7u32)]));
+}
+
+#[test]
+fn encode_string_escapes() {
+    assert_eq!(
+        "\"quote \\\" backslash \\\\ newline \\n tab \\t bell \\u0007 こんにちは\"",
+        encode("quote \" backslash \\ newline \n tab \t bell \x07 こんにちは")
+    );
+}
+
+#[test]
+fn encode_keeps_number_text() {
+    let v = parse("[1.50, 1e3, 18446744073709551615]").unwrap();
+    assert_eq!("[1.50,1e3,18446744073709551615]", encode(&v));
+}
+
+#[test]
+fn round_trip_mesh() {
+    let mesh = Mesh {
+        points: vec![
+            Point { x: 0.5, y: -1.0 },
+            Point { x: 0.1, y: 1e10 },
+            Point {
+                x: 16777216.0,
+                y: 0.0,
+            },
+        ],
+        indeces: vec![0, 2, 1, 4294967295],
+    };
+
+    let json = encode(&mesh);
+
+    assert_eq!(Ok(mesh), decode::<Mesh>(&json));
+}
+
+#[test]
+fn pretty_default() {
+    let v = parse(
+        "{\"name\": \"mesh\", \"indeces\": [0, 1, 2], \"points\": [{\"x\": 1, \"y\": 2}], \"tags\": [\"a\"]}",
+    )
+    .unwrap();
+
+    let config = PrettyConfig {
+        sort_keys: true,
+        ..Default::default()
+    };
+
+    let expected = r#"{
+  "indeces": [0, 1, 2],
+  "name": "mesh",
+  "points": [
+    {
+      "x": 1,
+      "y": 2
+    }
+  ],
+  "tags": ["a"]
+}
+"#;
+
+    assert_eq!(expected, to_string_pretty(&v, &config));
+}
+
+#[test]
+fn pretty_wraps_long_arrays() {
+    let v = Value::Object(Map::from([(
+        "numbers".to_owned(),
+        (0..8u32).collect::<Vec<_>>().to_json(),
+    )]));
+
+    let config = PrettyConfig {
+        indent: Indent::Tabs,
+        inline_array_width: Some(40),
+        trailing_newline: false,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        "{\n\t\"numbers\": [0, 1, 2, 3, 4, 5, 6, 7]\n}",
+        to_string_pretty(&v, &config)
+    );
+
+    let config = PrettyConfig {
+        inline_array_width: Some(20),
+        ..config
+    };
+
+    assert_eq!(
+        "{\n\t\"numbers\": [\n\t\t0,\n\t\t1,\n\t\t2,\n\t\t3,\n\t\t4,\n\t\t5,\n\t\t6,\n\t\t7\n\t]\n}",
+        to_string_pretty(&v, &config)
+    );
+}
+
+#[test]
+fn pretty_empty_containers() {
+    let v = Value::Array(vec![Value::Array(vec![]), Value::Object(Map::new())]);
+
+    let config = PrettyConfig {
+        indent: Indent::Spaces(4),
+        inline_array_width: None,
+        ..Default::default()
+    };
+
+    assert_eq!("[\n    [],\n    {}\n]\n", to_string_pretty(&v, &config));
+}
+
+#[test]
+fn json_writer() {
+    let mut w = JsonWriter::new(String::new());
+
+    w.begin_object().unwrap();
+    w.key("name").unwrap();
+    w.value("tri\"angle").unwrap();
+    w.key("points").unwrap();
+    w.begin_array().unwrap();
+    for i in 0..3u32 {
+        w.begin_object().unwrap();
+        w.key("x").unwrap();
+        w.value(&i).unwrap();
+        w.key("y").unwrap();
+        w.value(&0.5f32).unwrap();
+        w.end_object().unwrap();
+    }
+    w.end_array().unwrap();
+    w.key("empty").unwrap();
+    w.begin_array().unwrap();
+    w.end_array().unwrap();
+    w.end_object().unwrap();
+
+    assert_eq!(
+        "{\"name\":\"tri\\\"angle\",\"points\":[{\"x\":0,\"y\":0.5},{\"x\":1,\"y\":0.5},{\"x\":2,\"y\":0.5}],\"empty\":[]}",
+        w.finish().unwrap()
+    );
+}
+
+#[test]
+fn json_writer_matches_encode() {
+    let v = parse("[{\"a\": 1.50}, \"\\u0001\\n\", -0, 1e3]").unwrap();
+
+    let mut buf = Vec::new();
+    let mut w = JsonWriter::from_io(&mut buf);
+    w.write_value(&v).unwrap();
+    w.finish().unwrap();
+
+    assert_eq!(encode(&v).as_bytes(), &buf[..]);
+}
+
+#[test]
+fn json_writer_enforces_structure() {
+    let mut w = JsonWriter::new(String::new());
+    w.begin_object().unwrap();
+    assert!(matches!(w.value(&1u32), Err(WriterError::MissingKey)));
+    assert!(matches!(w.end_array(), Err(WriterError::MismatchedEnd)));
+    w.key("a").unwrap();
+    assert!(matches!(w.key("b"), Err(WriterError::UnexpectedKey)));
+    assert!(matches!(w.end_object(), Err(WriterError::MissingValue)));
+    w.value(&1u32).unwrap();
+    assert!(matches!(w.finish(), Err(WriterError::Incomplete)));
+
+    let mut w = JsonWriter::new(String::new());
+    assert!(matches!(w.key("a"), Err(WriterError::UnexpectedKey)));
+    w.value(&1u32).unwrap();
+    assert!(matches!(w.value(&2u32), Err(WriterError::MultipleRoots)));
+    assert_eq!("1", w.finish().unwrap());
+}
+
+fn read_events(json: &str, chunk_size: usize) -> Result<Vec<Event<'static>>, ParseError> {
+    Reader::with_chunk_size(json.as_bytes(), chunk_size).collect()
+}
+
+#[test]
+fn reader_events() {
+    let json = "{\"name\": \"こんにちは\", \"values\": [12345, -1.5e3, true, null, []], \"o\": {}}";
+
+    let expected = vec![
+        Event::StartObject,
+        Event::Key("name".into()),
+        Event::String("こんにちは".into()),
+        Event::Key("values".into()),
+        Event::StartArray,
+        Event::Number(Number::from(12345)),
+        Event::Number(Number::from_f64(-1500.0)),
+        Event::Boolean(true),
+        Event::Null,
+        Event::StartArray,
+        Event::EndArray,
+        Event::EndArray,
+        Event::Key("o".into()),
+        Event::StartObject,
+        Event::EndObject,
+        Event::EndObject,
+    ];
+
+    for chunk_size in [1, 2, 3, 7, 4096] {
+        assert_eq!(Ok(expected.clone()), read_events(json, chunk_size));
+    }
+}
+
+#[test]
+fn reader_error_positions() {
+    for chunk_size in [1, 5, 4096] {
+        let err = read_events("[\n  1,\n  2\n  3\n]", chunk_size).unwrap_err();
+        assert_eq!(ParseErrorKind::ExpectedCommaOrCloseBracket, err.kind);
+        assert_eq!(
+            Position {
+                offset: 13,
+                line: 4,
+                column: 3
+            },
+            err.position
+        );
+
+        let err = read_events("[\"ü\"] x", chunk_size).unwrap_err();
+        assert_eq!(ParseErrorKind::TrailingCharacters, err.kind);
+        assert_eq!(7, err.position.column);
+
+        let err = read_events("[1, tru", chunk_size).unwrap_err();
+        assert_eq!(ParseErrorKind::UnknownIdentifier, err.kind);
+    }
+}
+
+#[test]
+fn reader_invalid_utf8() {
+    let mut reader = Reader::with_chunk_size(&b"[\"a\xff\"]"[..], 2);
+    assert_eq!(Some(Ok(Event::StartArray)), reader.next());
+    assert_eq!(
+        ParseErrorKind::InvalidUtf8,
+        reader.next().unwrap().unwrap_err().kind
+    );
+    assert_eq!(None, reader.next());
+}
+
+struct FailingRead;
+
+impl std::io::Read for FailingRead {
+    fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("boom"))
+    }
+}
+
+#[test]
+fn reader_io_error() {
+    let err = Reader::new(FailingRead).next_event().unwrap_err();
+    assert_eq!(ParseErrorKind::Io(std::io::ErrorKind::Other), err.kind);
+}
+
+#[test]
+fn borrowed_strings() {
+    let json = "{\"plain\": \"abc\", \"escaped\": \"a\\nb\", \"list\": [\"x\", 1]}";
+    let v = parse_borrowed(json).unwrap();
+    let o = v.as_object().unwrap();
+
+    assert!(matches!(
+        o["plain"],
+        BorrowedValue::String(Cow::Borrowed("abc"))
+    ));
+    assert!(matches!(&o["escaped"], BorrowedValue::String(Cow::Owned(s)) if s == "a\nb"));
+    assert!(o.keys().all(|k| matches!(k, Cow::Borrowed(_))));
+
+    assert_eq!(parse(json).unwrap(), v.into_owned());
+}
+
+#[derive(Debug, PartialEq, Default)]
+struct Asset<'a> {
+    name: &'a str,
+    tags: Vec<Cow<'a, str>>,
+    size: u32,
+}
+
+impl<'a> FromBorrowedJSON<'a> for Asset<'a> {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        let o = v.as_object()?;
+
+        extract_borrowed_field(o, "name", &mut res.name)?;
+        extract_borrowed_field(o, "tags", &mut res.tags)?;
+        extract_borrowed_field(o, "size", &mut res.size)?;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn decode_borrowed_struct() {
+    let json = "{\"name\": \"rock\", \"tags\": [\"stone\", \"gr\\tey\"], \"size\": 3}";
+
+    let asset: Asset = decode_borrowed(json).unwrap();
+
+    assert_eq!("rock", asset.name);
+    assert!(matches!(asset.tags[0], Cow::Borrowed("stone")));
+    assert_eq!("gr\tey", asset.tags[1]);
+    assert_eq!(3, asset.size);
+
+    let err = match decode_borrowed::<Asset>("{\"name\": \"r\\tock\", \"tags\": [], \"size\": 3}") {
+        Err(JSONError::DecodeError(e)) => e,
+        other => panic!("Expected a decode error, got {:?}", other),
+    };
+    assert_eq!(
+        "$.name: string contains escape sequences and cannot be borrowed",
+        err.to_string()
+    );
+}
+
+#[derive(Debug, PartialEq, Default)]
+struct Layer<'a> {
+    visible: bool,
+    tags: HashSet<&'a str>,
+    counts: BTreeMap<String, u32>,
+    names: Map<String, Cow<'a, str>>,
+}
+
+impl<'a> FromBorrowedJSON<'a> for Layer<'a> {
+    fn from_borrowed_json(v: &BorrowedValue<'a>, res: &mut Self) -> Result<(), DecodeError> {
+        let o = v.as_object()?;
+
+        extract_borrowed_field(o, "visible", &mut res.visible)?;
+        extract_borrowed_field(o, "tags", &mut res.tags)?;
+        extract_borrowed_field(o, "counts", &mut res.counts)?;
+        extract_borrowed_field(o, "names", &mut res.names)?;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn decode_borrowed_collections() {
+    let json = r#"{"visible": true, "tags": ["a", "b", "a"], "counts": {"y": 2, "x": 1},
+        "names": {"b": "bee", "a": "ay"}}"#;
+    let layer: Layer = decode_borrowed(json).unwrap();
+    assert!(layer.visible);
+    assert_eq!(HashSet::from(["a", "b"]), layer.tags);
+    assert_eq!(
+        decode::<BTreeMap<String, u32>>(r#"{"x": 1, "y": 2}"#),
+        Ok(layer.counts)
+    );
+    assert_eq!(vec!["b", "a"], layer.names.keys().collect::<Vec<_>>());
+    assert!(matches!(layer.names["a"], Cow::Borrowed("ay")));
+
+    assert_eq!(Ok(false), decode_borrowed::<bool>("false"));
+    let err = decode_borrowed::<HashSet<u32>>("[1, true]").unwrap_err();
+    assert_eq!("$[1]: expected u32, got boolean", err.to_string());
+    let err = decode_borrowed::<BTreeMap<u32, bool>>(r#"{"1": true, "x": false}"#).unwrap_err();
+    assert_eq!("$.x: invalid key", err.to_string());
+    let err = decode_borrowed::<Map<String, bool>>(r#"{"a": 1}"#).unwrap_err();
+    assert_eq!("$.a: expected boolean, got number", err.to_string());
+}
+
+#[test]
+fn borrowed_parse_options() {
+    let deep = "[".repeat(100_000);
+    assert_eq!(
+        ParseErrorKind::DepthLimitExceeded(128),
+        parse_borrowed(&deep).unwrap_err().kind
+    );
+
+    let options = ParseOptions {
+        duplicate_keys: DuplicateKeys::CollectAll,
+        max_string_len: Some(3),
+        ..Default::default()
+    };
+    let v = parse_borrowed_with("{\"a\": \"x\", \"b\": 1, \"a\": \"y\"}", &options).unwrap();
+    assert_eq!(
+        parse("{\"a\": [\"x\", \"y\"], \"b\": 1}"),
+        Ok(v.into_owned())
+    );
+
+    let err = parse_borrowed_with("[\"abcd\"]", &options).unwrap_err();
+    assert_eq!(
+        (ParseErrorKind::StringTooLong(3), 1),
+        (err.kind, err.position.offset)
+    );
+}
+
+#[test]
+fn borrowed_deep_documents() {
+    const DEPTH: usize = 1_000_000;
+
+    let mut json = String::new();
+    for i in 0..DEPTH {
+        json.push_str(if i % 2 == 0 { "[\"s\"," } else { "{\"k\":" });
+    }
+    json.push_str("null");
+    for i in (0..DEPTH).rev() {
+        json.push(if i % 2 == 0 { ']' } else { '}' });
+    }
+
+    let options = ParseOptions {
+        max_depth: None,
+        ..Default::default()
+    };
+    drop(parse_borrowed_with(&json, &options).unwrap());
+
+    let v = parse_borrowed_with(&json, &options).unwrap().into_owned();
+    let mut depth = 0;
+    let mut inner = &v;
+    loop {
+        inner = match inner {
+            Value::Array(a) => &a[1],
+            Value::Object(o) => &o["k"],
+            _ => break,
+        };
+        depth += 1;
+    }
+    assert_eq!(DEPTH, depth);
+}
+
+#[test]
+fn objects_keep_document_order() {
+    let json = "{\"z\":1,\"a\":2,\"m\":{\"y\":true,\"b\":null}}";
+    let v = parse(json).unwrap();
+    assert_eq!(json, v.to_string());
+
+    let mut o = v.as_object().unwrap().clone();
+    assert_eq!(
+        Some(Value::Number(1.into())),
+        o.insert("z".to_owned(), Value::Number(3.into()))
+    );
+    o.insert("c".to_owned(), Value::Null);
+    assert_eq!(vec!["z", "a", "m", "c"], o.keys().collect::<Vec<_>>());
+
+    assert_eq!(Some(Value::Number(2.into())), o.remove("a"));
+    assert_eq!(vec!["z", "m", "c"], o.keys().collect::<Vec<_>>());
+    assert_eq!(Some(2), o.index_of("c"));
+
+    // Order doesn't affect equality.
+    assert_eq!(parse("{\"b\":1,\"a\":2}"), parse("{\"a\":2,\"b\":1}"));
+}
+
+#[test]
+fn large_objects() {
+    let mut o = Map::new();
+    for i in 0..100 {
+        o.insert(format!("k{}", i), i);
+    }
+
+    for i in (0..100).step_by(3) {
+        assert_eq!(Some(i), o.remove(&format!("k{}", i)));
+    }
+
+    assert_eq!(66, o.len());
+    for i in 0..100 {
+        assert_eq!(i % 3 != 0, o.contains_key(&format!("k{}", i)));
+    }
+    assert_eq!(Some((&"k1".to_owned(), &1)), o.get_index(0));
+    assert_eq!(Some((&"k98".to_owned(), &98)), o.get_index(65));
+}
+
+#[test]
+fn object_backends() {
+    let json = "{\"b\":1,\"c\":2,\"a\":3}";
+
+    let sorted: BTreeMap<String, u32> = decode(json).unwrap();
+    assert_eq!("{\"a\":3,\"b\":1,\"c\":2}", encode(&sorted));
+
+    let hashed: HashMap<String, u32> = decode(json).unwrap();
+    assert_eq!(Some(&3), hashed.get("a"));
+
+    let ordered: Map<String, u32> = decode(json).unwrap();
+    assert_eq!(json, encode(&ordered));
+
+    let mut v = parse(json).unwrap();
+    if let Value::Object(o) = &mut v {
+        o.sort_keys();
+    }
+    assert_eq!("{\"a\":3,\"b\":1,\"c\":2}", v.to_string());
+}
+
+#[test]
+fn duplicate_keys() {
+    let json = "{\"a\": 1, \"b\": [2],\n \"a\": 3, \"b\": 4, \"a\": 5}";
+    let options = |duplicate_keys| ParseOptions {
+        duplicate_keys,
+        ..Default::default()
+    };
+    let with = |policy| parse_with(json, &options(policy)).map(|v| v.to_string());
+
+    // Last wins unless told otherwise.
+    assert_eq!(
+        Ok("{\"a\":5,\"b\":4}".to_owned()),
+        parse(json).map(|v| v.to_string())
+    );
+    assert_eq!(
+        Ok("{\"a\":5,\"b\":4}".to_owned()),
+        with(DuplicateKeys::LastWins)
+    );
+    assert_eq!(
+        Ok("{\"a\":1,\"b\":[2]}".to_owned()),
+        with(DuplicateKeys::FirstWins)
+    );
+    assert_eq!(
+        Ok("{\"a\":[1,3,5],\"b\":[[2],4]}".to_owned()),
+        with(DuplicateKeys::CollectAll)
+    );
+
+    let err = parse_with(json, &options(DuplicateKeys::Error)).unwrap_err();
+    assert_eq!(
+        ParseErrorKind::DuplicateKey {
+            key: "a".to_owned(),
+            first: Position {
+                offset: 1,
+                line: 1,
+                column: 2
+            }
+        },
+        err.kind
+    );
+    assert_eq!(
+        Position {
+            offset: 20,
+            line: 2,
+            column: 2
+        },
+        err.position
+    );
+    assert_eq!(
+        "Duplicate key \"a\" (first defined at line 1, column 2) at line 2, column 2",
+        err.to_string()
+    );
+
+    let first_wins = options(DuplicateKeys::FirstWins);
+    let m: HashMap<String, u32> = decode_with("{\"a\": 1, \"a\": 2}", &first_wins).unwrap();
+    assert_eq!(1, m["a"]);
+}
+
+#[test]
+fn parse_limits() {
+    let err = |json: &str, options: ParseOptions| {
+        let e = parse_with(json, &options).unwrap_err();
+        (e.kind, e.position.offset)
+    };
+
+    // The default depth limit stops runaway nesting before it can overflow the stack.
+    let deep = "[".repeat(100_000);
+    assert_eq!(
+        (ParseErrorKind::DepthLimitExceeded(128), 128),
+        err(&deep, Default::default())
+    );
+
+    let nested = "[[[1]]]";
+    let limited = |f: fn(&mut ParseOptions)| {
+        let mut options = ParseOptions::default();
+        f(&mut options);
+        options
+    };
+    assert!(parse_with(nested, &limited(|o| o.max_depth = Some(3))).is_ok());
+    assert_eq!(
+        (ParseErrorKind::DepthLimitExceeded(2), 2),
+        err(nested, limited(|o| o.max_depth = Some(2)))
+    );
+
+    assert_eq!(
+        (ParseErrorKind::InputTooLong(5), 5),
+        err(nested, limited(|o| o.max_input_len = Some(5)))
+    );
+
+    let strings = "{\"ab\": \"a\\tc\", \"abcd\": 1}";
+    assert!(parse_with(strings, &limited(|o| o.max_string_len = Some(4))).is_ok());
+    assert_eq!(
+        (ParseErrorKind::StringTooLong(3), 15),
+        err(strings, limited(|o| o.max_string_len = Some(3)))
+    );
+
+    let array = "[1, 2,  3]";
+    assert!(parse_with(array, &limited(|o| o.max_array_len = Some(3))).is_ok());
+    assert_eq!(
+        (ParseErrorKind::ArrayTooLong(2), 8),
+        err(array, limited(|o| o.max_array_len = Some(2)))
+    );
+
+    assert_eq!(
+        (ParseErrorKind::ObjectTooLong(1), 15),
+        err(strings, limited(|o| o.max_object_len = Some(1)))
+    );
+
+    // Every value counts, including containers.
+    assert!(parse_with(nested, &limited(|o| o.max_nodes = Some(4))).is_ok());
+    assert_eq!(
+        (ParseErrorKind::TooManyNodes(4), 9),
+        err("[[1, 2], 3]", limited(|o| o.max_nodes = Some(4)))
+    );
+}
+
+#[test]
+fn deep_documents() {
+    const DEPTH: usize = 1_000_000;
+
+    let json = "[".repeat(DEPTH) + &"]".repeat(DEPTH);
+    let options = ParseOptions {
+        max_depth: None,
+        ..Default::default()
+    };
+    let v = parse_with(&json, &options).unwrap();
+
+    let mut depth = 0;
+    let mut inner = &v;
+    while let Value::Array(a) = inner {
+        depth += 1;
+        match a.first() {
+            Some(elem) => inner = elem,
+            None => break,
+        }
+    }
+    assert_eq!(DEPTH, depth);
+    drop(v);
+
+    let mut v = Value::Null;
+    for i in 0..DEPTH {
+        v = if i % 2 == 0 {
+            Value::Array(vec![v, Value::Boolean(true)])
+        } else {
+            Value::Object(Map::from([("k".to_owned(), v)]))
+        };
+    }
+    drop(v);
+}
+
+#[test]
+fn empty_containers() {
+    assert_eq!(Ok(Value::Array(vec![])), parse("[]"));
+    assert_eq!(Ok(Value::Object(Map::new())), parse(" { } "));
+    assert_eq!("[[],{}]", parse("[[], {}]").unwrap().to_string());
+}
+
+#[test]
+fn strict_syntax() {
+    let strict = ParseOptions {
+        syntax: Syntax::Strict,
+        ..Default::default()
+    };
+    let kind = |json: &str| parse_with(json, &strict).map_err(|e| (e.kind, e.position.offset));
+
+    assert_eq!(Err((ParseErrorKind::InvalidNumber, 1)), kind("[01]"));
+    assert_eq!(Err((ParseErrorKind::InvalidNumber, 0)), kind("-00"));
+    assert_eq!(Err((ParseErrorKind::InvalidNumber, 2)), kind("1."));
+    assert_eq!(Err((ParseErrorKind::InvalidNumber, 1)), kind("-"));
+    assert_eq!(
+        Err((ParseErrorKind::ControlCharacterInString, 2)),
+        kind("\"a\tb\"")
+    );
+    assert_eq!(
+        Err((ParseErrorKind::InvalidEscape('q'), 2)),
+        kind("\"a\\qb\"")
+    );
+    assert!(kind("[0, -0, 0.5, 10, 1e05]").is_ok());
+
+    // The lenient default accepts all of these except the lone minus.
+    assert_eq!(Ok("[1]".to_owned()), parse("[01]").map(|v| v.to_string()));
+    assert_eq!(Ok(Value::Number(1.into())), parse("1."));
+    assert!(parse("-").is_err());
+    assert_eq!(Ok(Value::String("a\tb".to_owned())), parse("\"a\tb\""));
+    assert_eq!(Ok(Value::String("aqb".to_owned())), parse("\"a\\qb\""));
+}
+
+#[test]
+fn unicode_escapes() {
+    assert_eq!(
+        Ok(Value::String(
+            "a\u{e9}\u{30af}\u{1F600}\u{10FFFF}".to_owned()
+        )),
+        parse(r#""\u0061\u00E9\u30af\ud83d\uDE00\uDBFF\uDFFF""#)
+    );
+    assert_eq!(
+        Ok(Value::String("\u{FFFF}".to_owned())),
+        parse(r#""\uffff""#)
+    );
+
+    let kind = |json: &str| parse(json).map_err(|e| (e.kind, e.position.offset));
+    assert_eq!(
+        Err((ParseErrorKind::InvalidUnicodeEscape, 2)),
+        kind(r#""a\u12x4""#)
+    );
+    assert_eq!(
+        Err((ParseErrorKind::InvalidUnicodeEscape, 1)),
+        kind(r#""\u12""#)
+    );
+    assert_eq!(
+        Err((ParseErrorKind::LoneSurrogate, 2)),
+        kind(r#""a\ud800b""#)
+    );
+    assert_eq!(
+        Err((ParseErrorKind::LoneSurrogate, 1)),
+        kind(r#""\udc00\ud800""#)
+    );
+}
+
+#[test]
+fn lone_surrogates() {
+    let json = r#"["a\ud800b", "\udc00\ud83d\ude00\ud800", {"k\udfff": 1}]"#;
+    let with = |lone_surrogates| {
+        let options = ParseOptions {
+            lone_surrogates,
+            ..Default::default()
+        };
+        parse_with(json, &options).unwrap()
+    };
+
+    let replaced = with(LoneSurrogates::Replace);
+    assert_eq!(
+        "[\"a\u{FFFD}b\",\"\u{FFFD}\u{1F600}\u{FFFD}\",{\"k\u{FFFD}\":1}]",
+        replaced.to_string()
+    );
+
+    let preserved = with(LoneSurrogates::Preserve);
+    let a = preserved.as_array().unwrap();
+    assert_eq!(Value::RawString(b"a\xED\xA0\x80b".to_vec()), a[0]);
+    assert_eq!(ValueKind::String, a[0].kind());
+    assert!(a[0].as_string().is_err());
+
+    // Written back out, the surrogates are escaped again.  Keys can only be replaced.
+    assert_eq!(
+        "[\"a\\ud800b\",\"\\udc00\u{1F600}\\ud800\",{\"k\u{FFFD}\":1}]",
+        preserved.to_string()
+    );
+    assert_eq!(
+        preserved.to_string(),
+        parse_with(
+            &preserved.to_string(),
+            &ParseOptions {
+                lone_surrogates: LoneSurrogates::Preserve,
+                ..Default::default()
+            }
+        )
+        .unwrap()
+        .to_string()
+    );
+}
+
+#[test]
+fn parse_never_panics() {
+    const DOCUMENTS: &[&[u8]] = &[
+        br#"{"a": [1, -2.5e3, true, null], "b": {"c": "\u00e9\ud83d\ude00"}, "a": "x"}"#,
+        br#"[[[{"k": [0.1, 1E+2, "\n\t\"\\"]}]], {}, [], "", -0]"#,
+        b"[\"\xED\xA0\x80\", \"\\ud800\", \"\xff\"]",
+    ];
+    const SUBSTITUTES: &[u8] = b"\0\"\\[]{},:-+.eE0uxI/*' \xff\xc3";
+
+    let options = [
+        ParseOptions::default(),
+        ParseOptions {
+            syntax: Syntax::Strict,
+            duplicate_keys: DuplicateKeys::Error,
+            ..Default::default()
+        },
+        ParseOptions {
+            lone_surrogates: LoneSurrogates::Preserve,
+            duplicate_keys: DuplicateKeys::CollectAll,
+            max_nodes: Some(6),
+            ..Default::default()
+        },
+        ParseOptions {
+            syntax: Syntax::Relaxed,
+            ..Default::default()
+        },
+    ];
+
+    let check = |input: &[u8]| {
+        for o in &options {
+            let _ = parse_bytes_with(input, o);
+        }
+        for event in Reader::new(input) {
+            if event.is_err() {
+                break;
+            }
+        }
+        if let Ok(s) = std::str::from_utf8(input) {
+            for o in &options {
+                let _ = parse_recovering(s, o);
+            }
+            let _ = parse_borrowed(s);
+            let _ = decode::<HashMap<String, Vec<Option<i64>>>>(s);
+            let _ = decode::<Vec<BTreeMap<String, f64>>>(s);
+            let _ = decode_borrowed::<Vec<Option<Cow<str>>>>(s);
+        }
+    };
+
+    for doc in DOCUMENTS {
+        for end in 0..=doc.len() {
+            check(&doc[..end]);
+        }
+        for i in 0..doc.len() {
+            for &b in SUBSTITUTES {
+                let mut mutated = doc.to_vec();
+                mutated[i] = b;
+                check(&mutated);
+            }
+        }
+    }
+}
+
+#[test]
+fn relaxed_syntax() {
+    let relaxed = ParseOptions {
+        syntax: Syntax::Relaxed,
+        ..Default::default()
+    };
+    let parse_relaxed = |json: &str| parse_with(json, &relaxed);
+
+    let json = r#"// Written by hand.
+{
+    unquoted: 'single \'quoted\'',
+    $id_2: "tab\	and\
+continued",
+    /* a block
+       comment */
+    numbers: [0x1F, -0XfF, +1, .5, 5., -.25e1, +Infinity, -Infinity, NaN,],
+    escapes: '\x41\v\0',
+    "trailing": {a: 1,},
+}
+"#;
+    let v = parse_relaxed(json).unwrap();
+    assert_eq!(
+        r#"{"unquoted":"single 'quoted'","$id_2":"tab\tandcontinued","numbers":[31,-255,1,0.5,5.0,-2.5,null,null,null],"escapes":"A\u000b\u0000","trailing":{"a":1}}"#,
+        v.to_string()
+    );
+
+    let numbers = v.as_object().unwrap()["numbers"].as_array().unwrap();
+    match (&numbers[6], &numbers[7], &numbers[8]) {
+        (Value::Number(inf), Value::Number(neg_inf), Value::Number(nan)) => {
+            assert_eq!(f64::INFINITY, inf.to_f64());
+            assert_eq!(f64::NEG_INFINITY, neg_inf.to_f64());
+            assert!(nan.to_f64().is_nan());
+        }
+        _ => panic!("expected numbers"),
+    }
+    assert_eq!(
+        Ok(Value::Number(u64::MAX.into())),
+        parse_relaxed("0xFFFFFFFFFFFFFFFF")
+    );
+    assert_eq!(
+        Ok(Value::Number(i64::MIN.into())),
+        parse_relaxed("-0x8000000000000000")
+    );
+    assert_eq!(
+        Ok(Value::String("a\r\nb".to_owned())),
+        parse_relaxed("'a\\r\\nb' // trailing comment")
+    );
+    assert_eq!(Ok(Value::Array(vec![])), parse_relaxed("[/**/]"));
+
+    let kind = |json: &str| parse_relaxed(json).map_err(|e| (e.kind, e.position.offset));
+    assert_eq!(
+        Err((ParseErrorKind::UnterminatedComment, 3)),
+        kind("[1 /* 2 ]")
+    );
+    assert_eq!(Err((ParseErrorKind::ExpectedValue, 1)), kind("[,]"));
+    assert_eq!(Err((ParseErrorKind::ExpectedValue, 3)), kind("[1,,]"));
+    assert_eq!(Err((ParseErrorKind::KeyMustBeString, 1)), kind("{,}"));
+    assert_eq!(Err((ParseErrorKind::InvalidNumber, 2)), kind("0x"));
+    assert_eq!(Err((ParseErrorKind::InvalidNumber, 1)), kind("."));
+    assert_eq!(Err((ParseErrorKind::InvalidEscape('1'), 1)), kind(r"'\1'"));
+    assert_eq!(Err((ParseErrorKind::InvalidEscape('0'), 1)), kind(r"'\01'"));
+    assert_eq!(
+        Err((ParseErrorKind::InvalidEscape('x'), 1)),
+        kind(r"'\xg0'")
+    );
+    assert_eq!(Err((ParseErrorKind::NewlineInString, 2)), kind("'a\nb'"));
+    assert_eq!(
+        Err((ParseErrorKind::UnknownIdentifier, 0)),
+        kind("Infinite")
+    );
+
+    // None of it is accepted by default.
+    for json in [
+        "[1,]", "{a: 1}", "'a'", "// c\n1", "0x1", "+1", ".5", "Infinity",
+    ] {
+        assert!(parse(json).is_err(), "{}", json);
+    }
+}
+
+#[test]
+fn ndjson() {
+    let input = "{\"a\": 1}\r\n\n[1, 2]\n  \n{oops}\n\"last\"";
+    let lines: Vec<_> = NdjsonReader::new(input.as_bytes()).collect();
+    assert_eq!(4, lines.len());
+    assert_eq!(Ok(parse("{\"a\": 1}").unwrap()), lines[0]);
+    assert_eq!(Ok(parse("[1, 2]").unwrap()), lines[1]);
+    assert_eq!(Ok(Value::String("last".to_owned())), lines[3]);
+
+    // Positions are within the whole input.
+    let error = lines[2].as_ref().unwrap_err();
+    assert_eq!(5, error.line);
+    assert_eq!(
+        JSONError::ParseError(ParseError {
+            kind: ParseErrorKind::KeyMustBeString,
+            position: Position {
+                offset: 22,
+                line: 5,
+                column: 2
+            }
+        }),
+        error.error
+    );
+
+    let mut reader = NdjsonReader::new(input.as_bytes()).skip_invalid_lines(true);
+    assert_eq!(3, reader.by_ref().count());
+    assert_eq!(1, reader.skipped());
+
+    // Decoding failures are reported the same way.
+    let decoded: Vec<_> = NdjsonReader::new("[1]\n[2, 3]\n{}\n".as_bytes())
+        .decode::<Vec<u32>>()
+        .collect();
+    assert_eq!(Ok(vec![1]), decoded[0]);
+    assert_eq!(Ok(vec![2, 3]), decoded[1]);
+    let error = decoded[2].as_ref().unwrap_err();
+    assert_eq!(3, error.line);
+    assert!(matches!(error.error, JSONError::DecodeError(_)));
+
+    let mut decoded = NdjsonReader::new("[1]\n{}\n[2]".as_bytes())
+        .skip_invalid_lines(true)
+        .decode::<Vec<u32>>();
+    assert_eq!(
+        vec![vec![1], vec![2]],
+        decoded.by_ref().map(Result::unwrap).collect::<Vec<_>>()
+    );
+    assert_eq!(1, decoded.reader().skipped());
+
+    let mut writer = NdjsonWriter::new(Vec::new());
+    writer.value(&vec![1u32, 2]).unwrap();
+    writer
+        .write_value(&Value::String("two\nlines".to_owned()))
+        .unwrap();
+    writer.write_value(&parse("{\"a\": {}}").unwrap()).unwrap();
+    let out = writer.into_inner();
+    assert_eq!(
+        "[1,2]\n\"two\\nlines\"\n{\"a\":{}}\n",
+        String::from_utf8(out.clone()).unwrap()
+    );
+    assert_eq!(3, NdjsonReader::new(&out[..]).map(Result::unwrap).count());
+}
+
+#[test]
+fn ndjson_line_too_long() {
+    // Only the first max_input_len + 1 bytes of a line are kept, but the rest still counts.
+    let options = ParseOptions {
+        max_input_len: Some(10),
+        ..Default::default()
+    };
+    let input = format!("[1]\n\"{}\"\n[2]\n{{oops}}\n", "a".repeat(100_000));
+    let lines: Vec<_> = NdjsonReader::with_options(input.as_bytes(), options.clone()).collect();
+    assert_eq!(4, lines.len());
+    assert_eq!(Ok(parse("[1]").unwrap()), lines[0]);
+    let error = lines[1].as_ref().unwrap_err();
+    assert_eq!(2, error.line);
+    assert_eq!(
+        JSONError::ParseError(ParseError {
+            kind: ParseErrorKind::InputTooLong(10),
+            position: Position {
+                offset: 14,
+                line: 2,
+                column: 11
+            }
+        }),
+        error.error
+    );
+    assert_eq!(Ok(parse("[2]").unwrap()), lines[2]);
+    let error = lines[3].as_ref().unwrap_err();
+    assert_eq!(4, error.line);
+    assert!(matches!(
+        &error.error,
+        JSONError::ParseError(ParseError { position, .. }) if position.offset == 100_012
+    ));
+
+    // The same, when the rest of the line is passed over a little at a time.
+    let source = std::io::BufReader::with_capacity(7, input.as_bytes());
+    let small: Vec<_> = NdjsonReader::with_options(source, options.clone()).collect();
+    assert_eq!(lines, small);
+
+    let mut reader = NdjsonReader::with_options(input.as_bytes(), options).skip_invalid_lines(true);
+    assert_eq!(2, reader.by_ref().count());
+    assert_eq!(2, reader.skipped());
+}
+
+#[test]
+fn concatenated_values() {
+    let input = br#"{"a":1}{"b":2} 3
+  [true]"x"null"#;
+    let values: Vec<_> = Concatenated::new(input).map(Result::unwrap).collect();
+    assert_eq!(
+        vec![
+            (0, parse(r#"{"a":1}"#).unwrap()),
+            (7, parse(r#"{"b":2}"#).unwrap()),
+            (15, Value::Number(3.into())),
+            (19, parse("[true]").unwrap()),
+            (25, Value::String("x".to_owned())),
+            (28, Value::Null),
+        ],
+        values
+    );
+
+    assert_eq!(0, Concatenated::new(b" \n ").count());
+
+    // Iteration stops at the first error.
+    let mut values = Concatenated::new(b"[1] [2 {}");
+    assert_eq!(Some(Ok((0, parse("[1]").unwrap()))), values.next());
+    assert_eq!(
+        Some(ParseErrorKind::ExpectedCommaOrCloseBracket),
+        values.next().and_then(|r| r.err()).map(|e| e.kind)
+    );
+    assert_eq!(None, values.next());
+
+    let relaxed = ParseOptions {
+        syntax: Syntax::Relaxed,
+        ..Default::default()
+    };
+    let values: Vec<_> = Concatenated::with_options(b"/* one */ 1 // two\n{a: 2,}", relaxed)
+        .map(|r| r.unwrap().0)
+        .collect();
+    assert_eq!(vec![10, 19], values);
+}
+
+#[test]
+fn json_text_sequences() {
+    let mut writer = JsonSeqWriter::new(Vec::new());
+    writer.value(&vec![1u32]).unwrap();
+    writer.write_value(&Value::Number(2.into())).unwrap();
+    let out = writer.into_inner();
+    assert_eq!(b"\x1e[1]\n\x1e2\n", &out[..]);
+    let values: Vec<_> = JsonSeqReader::new(&out[..]).map(Result::unwrap).collect();
+    assert_eq!(vec![parse("[1]").unwrap(), Value::Number(2.into())], values);
+
+    // The second record was cut short and the fourth is a number without its newline, so it may
+    // have been too.  Reading recovers at the next separator.
+    let input = b"\x1e{\"a\":1}\n\x1e{\"b\":\x1e\x1e\"c\"\n\x1e12";
+    let results: Vec<_> = JsonSeqReader::new(&input[..]).collect();
+    assert_eq!(4, results.len());
+    assert_eq!(Ok(parse("{\"a\":1}").unwrap()), results[0]);
+    assert_eq!(
+        Err((ParseErrorKind::UnexpectedEof, 15)),
+        results[1].clone().map_err(|e| (e.kind, e.position.offset))
+    );
+    assert_eq!(Ok(Value::String("c".to_owned())), results[2]);
+    assert_eq!(
+        Err((ParseErrorKind::TruncatedRecord, 24)),
+        results[3].clone().map_err(|e| (e.kind, e.position.offset))
+    );
+
+    let results: Vec<_> = JsonSeqReader::new(&b"[1]\n\x1e[2]\n"[..]).collect();
+    assert_eq!(
+        Err((ParseErrorKind::MissingRecordSeparator, 0)),
+        results[0].clone().map_err(|e| (e.kind, e.position.offset))
+    );
+    assert_eq!(Ok(parse("[2]").unwrap()), results[1]);
+}
+
+#[test]
+fn json_seq_record_too_long() {
+    let options = ParseOptions {
+        max_input_len: Some(10),
+        ..Default::default()
+    };
+    let input = format!(
+        "\x1e[1]\n\x1e\"{}\n\"\n\x1e[2]\n\x1e{{oops}}\n",
+        "a\n".repeat(50_000)
+    );
+    let results: Vec<_> = JsonSeqReader::with_options(input.as_bytes(), options.clone()).collect();
+    let source = std::io::BufReader::with_capacity(7, input.as_bytes());
+    let small: Vec<_> = JsonSeqReader::with_options(source, options).collect();
+    assert_eq!(results, small);
+    assert_eq!(4, results.len());
+    assert_eq!(Ok(parse("[1]").unwrap()), results[0]);
+    assert_eq!(
+        Err((ParseErrorKind::InputTooLong(10), 16)),
+        results[1].clone().map_err(|e| (e.kind, e.position.offset))
+    );
+    assert_eq!(Ok(parse("[2]").unwrap()), results[2]);
+    // Lines in the part that wasn't kept are still counted.
+    let error = results[3].clone().unwrap_err();
+    assert_eq!(ParseErrorKind::KeyMustBeString, error.kind);
+    assert_eq!(
+        Position {
+            offset: 100_017,
+            line: 50_005,
+            column: 3
+        },
+        error.position
+    );
+}
+
+// The value parse_recovering produces, and the kind and span of each diagnostic.
+fn recovered(json: &str) -> (String, Vec<(ParseErrorKind, usize, usize)>) {
+    let r = parse_recovering(json, &ParseOptions::default());
+    let spans = r
+        .diagnostics
+        .iter()
+        .map(|d| (d.kind.clone(), d.span.start.offset, d.span.end.offset))
+        .collect();
+    (r.value.to_string(), spans)
+}
+
+#[test]
+fn recovering_parser_valid_documents() {
+    // Documents without mistakes come out as they would from parse.
+    for json in ["[]", "{\"a\": [1, {\"b\": null}], \"c\": \"d\"}", " 1 "] {
+        let r = parse_recovering(json, &ParseOptions::default());
+        assert_eq!(parse(json).unwrap(), r.value);
+        assert!(r.diagnostics.is_empty());
+    }
+}
+
+#[test]
+fn recovering_parser_reports_every_mistake() {
+    assert_eq!(
+        (
+            r#"{"a":1,"b":[1,2,null,3],"c":null,"d":null,"e":5}"#.to_owned(),
+            vec![
+                (ParseErrorKind::ExpectedCommaOrCloseBrace, 8, 11),
+                (ParseErrorKind::ExpectedCommaOrCloseBracket, 16, 17),
+                (ParseErrorKind::ExpectedValue, 18, 19),
+                (ParseErrorKind::NewlineInString, 29, 38),
+                (ParseErrorKind::ExpectedCommaOrCloseBrace, 40, 43),
+                (ParseErrorKind::UnknownIdentifier, 45, 48),
+                (ParseErrorKind::KeyMustBeString, 50, 51),
+                (ParseErrorKind::KeyMustBeString, 55, 56),
+            ]
+        ),
+        recovered("{\"a\": 1 \"b\": [1 2,, 3], \"c\": \"unclosed\n \"d\": tru, e: 5,}")
+    );
+}
+
+#[test]
+fn recovering_parser_mismatched_brackets() {
+    // A closing bracket closes everything open inside the container it matches.
+    assert_eq!(
+        (
+            r#"[{"a":[1]},"b"]"#.to_owned(),
+            vec![(ParseErrorKind::ExpectedCommaOrCloseBracket, 9, 10)]
+        ),
+        recovered("[{\"a\": [1}, \"b\"]")
+    );
+    assert_eq!(
+        (
+            "[1]".to_owned(),
+            vec![(ParseErrorKind::ExpectedCommaOrCloseBracket, 2, 3)]
+        ),
+        recovered("[1}]")
+    );
+}
+
+#[test]
+fn recovering_parser_unexpected_eof() {
+    assert_eq!(
+        (
+            r#"{"a":[1,2],"b":null}"#.to_owned(),
+            vec![(ParseErrorKind::UnexpectedEof, 18, 18)]
+        ),
+        recovered("{\"a\": [1, 2], \"b\":")
+    );
+    assert_eq!(
+        (
+            "null".to_owned(),
+            vec![(ParseErrorKind::UnexpectedEof, 0, 0)]
+        ),
+        recovered("")
+    );
+}
+
+#[test]
+fn recovering_parser_trailing_characters() {
+    assert_eq!(
+        (
+            "1".to_owned(),
+            vec![(ParseErrorKind::TrailingCharacters, 2, 5)]
+        ),
+        recovered("1 2 3")
+    );
+}
+
+#[test]
+fn recovering_parser_unexpected_character() {
+    assert_eq!(
+        (
+            "[1,3]".to_owned(),
+            vec![
+                (ParseErrorKind::UnexpectedCharacter('@'), 3, 4),
+                (ParseErrorKind::ExpectedCommaOrCloseBracket, 5, 6),
+            ]
+        ),
+        recovered("[1 @ 3]")
+    );
+}
+
+#[test]
+fn recovering_parser_options() {
+    let options = ParseOptions {
+        max_depth: Some(2),
+        duplicate_keys: DuplicateKeys::Error,
+        ..Default::default()
+    };
+    let r = parse_recovering("[[[1, [2]]], {\"a\": 1, \"a\": 2}]", &options);
+    assert_eq!("[[null],{\"a\":1}]", r.value.to_string());
+    assert_eq!(
+        vec![
+            ParseErrorKind::DepthLimitExceeded(2),
+            ParseErrorKind::DuplicateKey {
+                key: "a".to_owned(),
+                first: Position {
+                    offset: 14,
+                    line: 1,
+                    column: 15
+                }
+            }
+        ],
+        r.diagnostics
+            .into_iter()
+            .map(|d| d.kind)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn push_parser_any_chunking() {
+    let json = "{\"name\": \"こんにちは\", \"values\": [12345, -1.5e3, true, null]}";
+    let expected = vec![
+        Event::StartObject,
+        Event::Key("name".into()),
+        Event::String("こんにちは".into()),
+        Event::Key("values".into()),
+        Event::StartArray,
+        Event::Number(Number::from(12345)),
+        Event::Number(Number::from_f64(-1500.0)),
+        Event::Boolean(true),
+        Event::Null,
+        Event::EndArray,
+        Event::EndObject,
+    ];
+
+    // Split everywhere, including inside tokens and inside multi-byte characters.
+    for chunk_size in [1, 2, 3, 5, 4096] {
+        let mut parser = PushParser::new();
+        let mut events = Vec::new();
+        for chunk in json.as_bytes().chunks(chunk_size) {
+            parser.feed(chunk);
+            for event in &mut parser {
+                events.push(event.unwrap());
+            }
+        }
+        assert!(parser.is_done());
+        parser.finish();
+        assert_eq!(None, parser.next());
+        assert_eq!(expected, events);
+    }
+}
+
+#[test]
+fn push_parser_events_as_soon_as_complete() {
+    let mut parser = PushParser::new();
+    parser.feed(b"[\"ab");
+    assert_eq!(Ok(Some(Event::StartArray)), parser.next_event());
+    assert_eq!(Ok(None), parser.next_event());
+    parser.feed(b"c\", 12");
+    assert_eq!(Ok(Some(Event::String("abc".into()))), parser.next_event());
+    assert_eq!(Ok(None), parser.next_event());
+    assert_eq!(1, parser.depth());
+    parser.feed(b"3]");
+    assert_eq!(
+        Ok(Some(Event::Number(Number::from(123)))),
+        parser.next_event()
+    );
+    assert_eq!(Ok(Some(Event::EndArray)), parser.next_event());
+    assert!(parser.is_done());
+}
+
+#[test]
+fn push_parser_top_level_number_waits_for_finish() {
+    let mut parser = PushParser::new();
+    parser.feed(b"42");
+    assert_eq!(Ok(None), parser.next_event());
+    parser.finish();
+    assert_eq!(
+        Ok(Some(Event::Number(Number::from(42)))),
+        parser.next_event()
+    );
+}
+
+#[test]
+fn push_parser_incomplete_document() {
+    let mut parser = PushParser::new();
+    parser.feed(b"[1,\n");
+    assert_eq!(2, parser.by_ref().count());
+    parser.finish();
+    let err = parser.next_event().unwrap_err();
+    assert_eq!(ParseErrorKind::UnexpectedEof, err.kind);
+    assert_eq!(2, err.position.line);
+    // Errors stick.
+    parser.feed(b"2]");
+    assert_eq!(Err(err), parser.next_event());
+}
+
+#[test]
+fn push_parser_trailing_characters() {
+    let mut parser = PushParser::new();
+    parser.feed(b"{}");
+    assert_eq!(2, parser.by_ref().count());
+    parser.feed(b"  \n x");
+    let err = parser.next_event().unwrap_err();
+    assert_eq!(ParseErrorKind::TrailingCharacters, err.kind);
+    assert_eq!(6, err.position.offset);
+}
+
+#[test]
+fn push_parser_yields_an_error_once() {
+    let mut parser = PushParser::new();
+    parser.feed(b"[1, }, 2]");
+    parser.finish();
+    let events: Vec<_> = parser.by_ref().collect();
+    assert_eq!(3, events.len());
+    assert_eq!(
+        ParseErrorKind::ExpectedValue,
+        events[2].as_ref().unwrap_err().kind
+    );
+    assert_eq!(None, parser.next());
+    // next_event still reports it.
+    assert!(parser.next_event().is_err());
+}
+
+#[test]
+fn push_parser_split_comments() {
+    let relaxed = ParseOptions {
+        syntax: Syntax::Relaxed,
+        ..Default::default()
+    };
+    let json =
+        "// leading\n[1, /* a long comment that */ 2 // to the end\n, 3] /* after */\u{2028} // last";
+    let expected = vec![
+        Event::StartArray,
+        Event::Number(Number::from(1)),
+        Event::Number(Number::from(2)),
+        Event::Number(Number::from(3)),
+        Event::EndArray,
+    ];
+    for chunk_size in [1, 2, 3, 7, 16, 4096] {
+        let mut parser = PushParser::with_options(relaxed.clone());
+        let mut events = Vec::new();
+        for chunk in json.as_bytes().chunks(chunk_size) {
+            parser.feed(chunk);
+            for event in &mut parser {
+                events.push(event.unwrap());
+            }
+        }
+        parser.finish();
+        assert_eq!(None, parser.next());
+        assert_eq!(expected, events, "{}", chunk_size);
+    }
+
+    // An unterminated comment is only an error once the input is finished.
+    let mut parser = PushParser::with_options(relaxed);
+    parser.feed(b"[1, /* a long comment that ");
+    assert_eq!(2, parser.by_ref().count());
+    parser.finish();
+    let err = parser.next_event().unwrap_err();
+    assert_eq!(ParseErrorKind::UnterminatedComment, err.kind);
+    assert_eq!(4, err.position.offset);
+}
+
+// Feeds json to a push parser in chunks of chunk_size, and returns the first error.
+fn push_error(json: &str, chunk_size: usize, options: &ParseOptions) -> Option<ParseError> {
+    let mut parser = PushParser::with_options(options.clone());
+    for chunk in json.as_bytes().chunks(chunk_size) {
+        parser.feed(chunk);
+        for event in &mut parser {
+            if let Err(e) = event {
+                return Some(e);
+            }
+        }
+    }
+    parser.finish();
+    parser.find_map(Result::err)
+}
+
+#[test]
+fn push_parser_split_invalid_character() {
+    let relaxed = ParseOptions {
+        syntax: Syntax::Relaxed,
+        ..Default::default()
+    };
+    for (json, options) in [
+        ("[1, \u{2028}]", ParseOptions::default()),
+        ("{\"a\": é}", ParseOptions::default()),
+        ("[\u{1F600}]", ParseOptions::default()),
+        ("[1 \u{2028} \u{2029} \u{1F600}]", relaxed),
+    ] {
+        let expected = parse_with(json, &options).unwrap_err();
+        assert!(matches!(
+            expected.kind,
+            ParseErrorKind::UnexpectedCharacter(c) if c != char::REPLACEMENT_CHARACTER
+        ));
+        for chunk_size in [1, 2, 3] {
+            assert_eq!(
+                Some(&expected),
+                push_error(json, chunk_size, &options).as_ref()
+            );
+            if options == ParseOptions::default() {
+                let mut reader = Reader::with_chunk_size(json.as_bytes(), chunk_size);
+                assert_eq!(Some(&expected), reader.find_map(Result::err).as_ref());
+            }
+        }
+    }
+}
+
+#[test]
+fn streaming_parse_options() {
+    let deep = "[".repeat(1000);
+    let err = Reader::new(deep.as_bytes()).find_map(Result::err).unwrap();
+    assert_eq!(
+        (ParseErrorKind::DepthLimitExceeded(128), 128),
+        (err.kind, err.position.offset)
+    );
+
+    let limited = |f: fn(&mut ParseOptions)| {
+        let mut options = ParseOptions::default();
+        f(&mut options);
+        options
+    };
+    let cases = [
+        ("[01]", limited(|o| o.syntax = Syntax::Strict)),
+        ("[[[1]]]", limited(|o| o.max_input_len = Some(5))),
+        (
+            "{\"ab\": \"a\\tc\"}",
+            limited(|o| o.max_string_len = Some(2)),
+        ),
+        ("[1, 2,  3]", limited(|o| o.max_array_len = Some(2))),
+        (
+            "{\"a\": 1, \"b\": 2}",
+            limited(|o| o.max_object_len = Some(1)),
+        ),
+        ("[[1, 2], 3]", limited(|o| o.max_nodes = Some(4))),
+        (
+            "{\"a\": 1, \"b\": [2],\n \"a\": 3}",
+            limited(|o| o.duplicate_keys = DuplicateKeys::Error),
+        ),
+    ];
+    for (json, options) in cases {
+        let expected = parse_with(json, &options).unwrap_err();
+        for chunk_size in [1, 3, 4096] {
+            assert_eq!(
+                Some(&expected),
+                push_error(json, chunk_size, &options).as_ref()
+            );
+        }
+    }
+
+    // Other duplicate key policies are up to whoever handles the events.
+    let options = ParseOptions {
+        duplicate_keys: DuplicateKeys::FirstWins,
+        lone_surrogates: LoneSurrogates::Preserve,
+        ..Default::default()
+    };
+    let json = "{\"a\": \"\\ud800\", \"a\": 2}";
+    let events: Result<Vec<_>, _> = Reader::with_options(json.as_bytes(), options).collect();
+    assert_eq!(
+        Ok(vec![
+            Event::StartObject,
+            Event::Key("a".into()),
+            Event::String("\u{FFFD}".into()),
+            Event::Key("a".into()),
+            Event::Number(Number::from(2)),
+            Event::EndObject,
+        ]),
+        events
+    );
+}
+
+#[test]
+fn json_pointer_rfc_examples() {
+    // The examples from RFC 6901.
+    let doc = parse(
+        r#"{"foo": ["bar", "baz"], "": 0, "a/b": 1, "c%d": 2, "e^f": 3, "g|h": 4, "i\\j": 5,
+            "k\"l": 6, " ": 7, "m~n": 8}"#,
+    )
+    .unwrap();
+    assert_eq!(Ok(&doc), doc.pointer(""));
+    assert_eq!(
+        Ok(&parse(r#"["bar", "baz"]"#).unwrap()),
+        doc.pointer("/foo")
+    );
+    assert_eq!(Ok(&Value::String("bar".into())), doc.pointer("/foo/0"));
+    let cases = [
+        ("/", 0),
+        ("/a~1b", 1),
+        ("/c%d", 2),
+        ("/e^f", 3),
+        ("/g|h", 4),
+        ("/i\\j", 5),
+        ("/k\"l", 6),
+        ("/ ", 7),
+        ("/m~0n", 8),
+    ];
+    for (pointer, n) in cases {
+        assert_eq!(Ok(&Value::Number(Number::from(n))), doc.pointer(pointer));
+        assert_eq!(pointer, JsonPointer::parse(pointer).unwrap().to_string());
+    }
+}
+
+#[test]
+fn json_pointer_escaping() {
+    // ~01 is ~1, not /.
+    let p = JsonPointer::parse("/~01").unwrap();
+    assert_eq!(["~1"], p.tokens());
+    let mut built = JsonPointer::root();
+    built.push("a/b");
+    built.push("m~n");
+    assert_eq!("/a~1b/m~0n", built.to_string());
+}
+
+fn points() -> Value {
+    parse(r#"{"points": [{"x": 1}, {"x": 2}], "name": "n"}"#).unwrap()
+}
+
+#[test]
+fn json_pointer_errors() {
+    // Errors say which segment failed.
+    let doc = points();
+    assert_eq!(
+        Ok(&Value::Number(Number::from(2))),
+        doc.pointer("/points/1/x")
+    );
+    let err = doc.pointer("/points/3/x").unwrap_err();
+    assert_eq!(PointerErrorKind::NotFound, err.kind);
+    assert_eq!("/points/3", err.path);
+    assert_eq!(r#""/points/3": not found"#, err.to_string());
+    let err = doc.pointer("/points/1/y").unwrap_err();
+    assert_eq!(
+        ("/points/1/y", PointerErrorKind::NotFound),
+        (&*err.path, err.kind)
+    );
+    let err = doc.pointer("/points/01").unwrap_err();
+    assert_eq!(PointerErrorKind::InvalidIndex, err.kind);
+    assert_eq!("/points/01", err.path);
+    let err = doc.pointer("/points/-").unwrap_err();
+    assert_eq!(PointerErrorKind::NotFound, err.kind);
+    let err = doc.pointer("/name/0").unwrap_err();
+    assert_eq!(PointerErrorKind::NotAContainer(ValueKind::String), err.kind);
+    assert_eq!("/name/0", err.path);
+    let err = doc.pointer("points").unwrap_err();
+    assert_eq!(PointerErrorKind::Syntax, err.kind);
+    let err = doc.pointer("/points/~2").unwrap_err();
+    assert_eq!(
+        ("/points/~2", PointerErrorKind::Syntax),
+        (&*err.path, err.kind)
+    );
+}
+
+#[test]
+fn json_pointer_mut() {
+    let mut doc = points();
+    *doc.pointer_mut("/points/0/x").unwrap() = Value::Null;
+    assert_eq!(Ok(&Value::Null), doc.pointer("/points/0/x"));
+}
+
+#[test]
+fn json_pointer_insert() {
+    let mut doc = points();
+    doc.pointer_insert("/points/1", parse(r#"{"x": 9}"#).unwrap())
+        .unwrap();
+    doc.pointer_insert("/points/-", parse(r#"{"x": 10}"#).unwrap())
+        .unwrap();
+    doc.pointer_insert("/points/0/y", Value::Boolean(true))
+        .unwrap();
+    doc.pointer_insert("/name", Value::String("m".into()))
+        .unwrap();
+    assert_eq!(
+        parse(r#"{"points": [{"x": 1, "y": true}, {"x": 9}, {"x": 2}, {"x": 10}], "name": "m"}"#),
+        Ok(doc.clone())
+    );
+    let err = doc.pointer_insert("/points/5", Value::Null).unwrap_err();
+    assert_eq!(
+        ("/points/5", PointerErrorKind::NotFound),
+        (&*err.path, err.kind)
+    );
+    let err = doc.pointer_insert("/missing/a", Value::Null).unwrap_err();
+    assert_eq!(
+        ("/missing", PointerErrorKind::NotFound),
+        (&*err.path, err.kind)
+    );
+
+    doc.pointer_insert("", Value::Null).unwrap();
+    assert_eq!(Value::Null, doc);
+}
+
+#[test]
+fn json_pointer_replace() {
+    let mut doc = points();
+    assert_eq!(
+        Ok(Value::Number(Number::from(2))),
+        doc.pointer_replace("/points/1/x", Value::Null)
+    );
+    assert_eq!(Ok(&Value::Null), doc.pointer("/points/1/x"));
+    let err = doc.pointer_replace("/points/1/z", Value::Null).unwrap_err();
+    assert_eq!(PointerErrorKind::NotFound, err.kind);
+}
+
+#[test]
+fn json_pointer_remove() {
+    let mut doc = points();
+    assert_eq!(Ok(Value::String("n".into())), doc.pointer_remove("/name"));
+    assert_eq!(
+        Ok(parse(r#"{"x": 1}"#).unwrap()),
+        doc.pointer_remove("/points/0")
+    );
+    assert_eq!(parse(r#"{"points": [{"x": 2}]}"#), Ok(doc.clone()));
+    let err = doc.pointer_remove("").unwrap_err();
+    assert_eq!(PointerErrorKind::RemoveRoot, err.kind);
+    let err = doc.pointer_remove("/points/3").unwrap_err();
+    assert_eq!(
+        ("/points/3", PointerErrorKind::NotFound),
+        (&*err.path, err.kind)
+    );
+}
+
+fn patched(doc: &str, patch: &str) -> Result<Value, PatchError> {
+    let patch: Patch = decode(patch).unwrap();
+    let mut doc = parse(doc).unwrap();
+    patch.apply(&mut doc).map(|_| doc)
+}
+
+#[test]
+fn json_patch_rfc_examples() {
+    // Examples from RFC 6902, appendix A.
+    let cases = [
+        (
+            r#"{"foo": "bar"}"#,
+            r#"[{"op": "add", "path": "/baz", "value": "qux"}]"#,
+            r#"{"baz": "qux", "foo": "bar"}"#,
+        ),
+        (
+            r#"{"foo": ["bar", "baz"]}"#,
+            r#"[{"op": "add", "path": "/foo/1", "value": "qux"}]"#,
+            r#"{"foo": ["bar", "qux", "baz"]}"#,
+        ),
+        (
+            r#"{"baz": "qux", "foo": "bar"}"#,
+            r#"[{"op": "remove", "path": "/baz"}]"#,
+            r#"{"foo": "bar"}"#,
+        ),
+        (
+            r#"{"foo": ["bar", "qux", "baz"]}"#,
+            r#"[{"op": "remove", "path": "/foo/1"}]"#,
+            r#"{"foo": ["bar", "baz"]}"#,
+        ),
+        (
+            r#"{"baz": "qux", "foo": "bar"}"#,
+            r#"[{"op": "replace", "path": "/baz", "value": "boo"}]"#,
+            r#"{"baz": "boo", "foo": "bar"}"#,
+        ),
+        (
+            r#"{"foo": {"bar": "baz", "waldo": "fred"}, "qux": {"corge": "grault"}}"#,
+            r#"[{"op": "move", "from": "/foo/waldo", "path": "/qux/thud"}]"#,
+            r#"{"foo": {"bar": "baz"}, "qux": {"corge": "grault", "thud": "fred"}}"#,
+        ),
+        (
+            r#"{"foo": ["all", "grass", "cows", "eat"]}"#,
+            r#"[{"op": "move", "from": "/foo/1", "path": "/foo/3"}]"#,
+            r#"{"foo": ["all", "cows", "eat", "grass"]}"#,
+        ),
+        (
+            r#"{"baz": "qux", "foo": ["a", 2, "c"]}"#,
+            r#"[{"op": "test", "path": "/baz", "value": "qux"},
+                {"op": "test", "path": "/foo/1", "value": 2.0}]"#,
+            r#"{"baz": "qux", "foo": ["a", 2, "c"]}"#,
+        ),
+        (
+            r#"{"foo": "bar"}"#,
+            r#"[{"op": "add", "path": "/child", "value": {"grandchild": {}}}]"#,
+            r#"{"foo": "bar", "child": {"grandchild": {}}}"#,
+        ),
+        (
+            r#"{"foo": ["bar"]}"#,
+            r#"[{"op": "add", "path": "/foo/-", "value": ["abc", "def"]}]"#,
+            r#"{"foo": ["bar", ["abc", "def"]]}"#,
+        ),
+        (
+            r#"{"a": {"b": 1}}"#,
+            r#"[{"op": "copy", "from": "/a", "path": "/c"}, {"op": "add", "path": "", "value": [1]}]"#,
+            r#"[1]"#,
+        ),
+    ];
+    for (doc, patch, expected) in cases {
+        assert_eq!(
+            Ok(parse(expected).unwrap()),
+            patched(doc, patch),
+            "{}",
+            patch
+        );
+    }
+}
+
+#[test]
+fn json_patch_is_atomic() {
+    // A failing operation leaves the document untouched.
+    let mut doc = parse(r#"{"baz": "qux", "foo": "bar"}"#).unwrap();
+    let original = doc.clone();
+    let patch: Patch = decode(
+        r#"[{"op": "remove", "path": "/foo"},
+            {"op": "test", "path": "/baz", "value": "bar"}]"#,
+    )
+    .unwrap();
+    let err = patch.apply(&mut doc).unwrap_err();
+    assert_eq!((1, PatchErrorKind::TestFailed), (err.index, err.kind));
+    assert_eq!(original, doc);
+}
+
+#[test]
+fn json_patch_errors() {
+    let err = patched(
+        r#"{"foo": "bar"}"#,
+        r#"[{"op": "add", "path": "/baz/bat", "value": "qux"}]"#,
+    )
+    .unwrap_err();
+    assert_eq!(r#"operation 0: "/baz": not found"#, err.to_string());
+    let err = patched(
+        r#"{"a": {"b": 1}}"#,
+        r#"[{"op": "move", "from": "/a", "path": "/a/b"}]"#,
+    )
+    .unwrap_err();
+    assert_eq!(PatchErrorKind::MoveIntoChild, err.kind);
+}
+
+#[test]
+fn json_patch_decode_errors() {
+    // Malformed patches fail to decode.
+    let err = decode::<Patch>(r#"[{"op": "add", "path": "/a"}]"#).unwrap_err();
+    assert_eq!("$[0].value: missing field", err.to_string());
+    let err = decode::<Patch>(r#"[{"op": "remove", "path": "/a"}, {"op": "frob", "path": ""}]"#)
+        .unwrap_err();
+    assert_eq!(r#"$[1].op: unknown operation "frob""#, err.to_string());
+    let err = decode::<Patch>(r#"[{"op": "remove", "path": "a"}]"#).unwrap_err();
+    assert_eq!(r#"$[0].path: "a": invalid JSON pointer"#, err.to_string());
+}
+
+#[test]
+fn json_patch_round_trip() {
+    // Patches round trip through Value.
+    let text =
+        r#"[{"op":"move","from":"/a~1b","path":"/c"},{"op":"test","path":"/d/0","value":[1]}]"#;
+    let patch: Patch = decode(text).unwrap();
+    assert_eq!(text, encode(&patch));
+}
+
+// Diffs a to b, checks that the patch turns a into b, and returns it encoded.
+fn diff_text(a: &str, b: &str) -> String {
+    let (a, b) = (parse(a).unwrap(), parse(b).unwrap());
+    let patch = diff(&a, &b);
+    let mut patched = a.clone();
+    patch.apply(&mut patched).unwrap();
+    assert_eq!(b, patched);
+    encode(&patch)
+}
+
+#[test]
+fn json_diff_ignores_number_representation() {
+    assert_eq!("[]", diff_text(r#"{"a": [1, 2.0]}"#, r#"{"a": [1.0, 2]}"#));
+}
+
+#[test]
+fn json_diff_objects() {
+    assert_eq!(
+        r#"[{"op":"remove","path":"/b"},{"op":"replace","path":"/a/x","value":2},{"op":"add","path":"/c","value":true}]"#,
+        diff_text(
+            r#"{"a": {"x": 1, "y": 1}, "b": 1}"#,
+            r#"{"a": {"x": 2, "y": 1}, "c": true}"#
+        )
+    );
+}
+
+#[test]
+fn json_diff_array_insertion() {
+    assert_eq!(
+        r#"[{"op":"add","path":"/0","value":0}]"#,
+        diff_text("[1, 2, 3, 4]", "[0, 1, 2, 3, 4]")
+    );
+}
+
+#[test]
+fn json_diff_array_removal() {
+    assert_eq!(
+        r#"[{"op":"remove","path":"/1"},{"op":"remove","path":"/1"},{"op":"add","path":"/2","value":5}]"#,
+        diff_text("[1, 2, 3, 4]", "[1, 4, 5]")
+    );
+}
+
+#[test]
+fn json_diff_array_elements_in_place() {
+    assert_eq!(
+        r#"[{"op":"replace","path":"/1/name","value":"b"},{"op":"add","path":"/2","value":{"name":"c"}}]"#,
+        diff_text(
+            r#"[{"name": "x"}, {"name": "a"}, {"name": "z"}]"#,
+            r#"[{"name": "x"}, {"name": "b"}, {"name": "c"}, {"name": "z"}]"#
+        )
+    );
+}
+
+#[test]
+fn json_diff_replaces_root() {
+    assert_eq!(
+        r#"[{"op":"replace","path":"","value":[1]}]"#,
+        diff_text(r#"{"a": 1}"#, "[1]")
+    );
+}
+
+#[test]
+fn json_diff_applies() {
+    diff_text(
+        r#"{"points": [{"x": 1}, {"x": 2}, {"x": 3}], "tags": ["a", "b", "c", "d"]}"#,
+        r#"{"points": [{"x": 2}, {"x": 3, "y": 1}, {"x": 4}], "tags": ["d", "c", "b", "a"]}"#,
+    );
+}
+
+#[test]
+fn merge_patch() {
+    // Examples from RFC 7396, appendix A.
+    let cases = [
+        (r#"{"a":"b"}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+        (r#"{"a":"b"}"#, r#"{"b":"c"}"#, r#"{"a":"b","b":"c"}"#),
+        (r#"{"a":"b"}"#, r#"{"a":null}"#, r#"{}"#),
+        (r#"{"a":"b","b":"c"}"#, r#"{"a":null}"#, r#"{"b":"c"}"#),
+        (r#"{"a":["b"]}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+        (r#"{"a":"c"}"#, r#"{"a":["b"]}"#, r#"{"a":["b"]}"#),
+        (
+            r#"{"a":{"b":"c"}}"#,
+            r#"{"a":{"b":"d","c":null}}"#,
+            r#"{"a":{"b":"d"}}"#,
+        ),
+        (r#"{"a":[{"b":"c"}]}"#, r#"{"a":[1]}"#, r#"{"a":[1]}"#),
+        (r#"["a","b"]"#, r#"["c","d"]"#, r#"["c","d"]"#),
+        (r#"{"a":"b"}"#, r#"["c"]"#, r#"["c"]"#),
+        (r#"{"a":"foo"}"#, "null", "null"),
+        (r#"{"a":"foo"}"#, r#""bar""#, r#""bar""#),
+        (r#"{"e":null}"#, r#"{"a":1}"#, r#"{"e":null,"a":1}"#),
+        (r#"[1,2]"#, r#"{"a":"b","c":null}"#, r#"{"a":"b"}"#),
+        (
+            r#"{}"#,
+            r#"{"a":{"bb":{"ccc":null}}}"#,
+            r#"{"a":{"bb":{}}}"#,
+        ),
+    ];
+    for (target, patch, expected) in cases {
+        let mut v = parse(target).unwrap();
+        v.merge_patch(&parse(patch).unwrap());
+        assert_eq!(parse(expected).unwrap(), v, "{} + {}", target, patch);
+    }
+
+    let a = parse(
+        r#"{"title": "Goodbye!", "author": {"givenName": "John", "familyName": "Doe"},
+        "tags": ["example", "sample"], "content": "This will be unchanged"}"#,
+    )
+    .unwrap();
+    let b = parse(
+        r#"{"title": "Hello!", "author": {"givenName": "John"}, "tags": ["example"],
+        "content": "This will be unchanged", "phoneNumber": "+01-123-456-7890"}"#,
+    )
+    .unwrap();
+    let patch = merge_diff(&a, &b);
+    assert_eq!(
+        r#"{"title":"Hello!","author":{"familyName":null},"tags":["example"],"phoneNumber":"+01-123-456-7890"}"#,
+        encode(&patch)
+    );
+    let mut patched = a.clone();
+    patched.merge_patch(&patch);
+    assert_eq!(b, patched);
+    assert_eq!("{}", encode(&merge_diff(&a, &a)));
+    assert_eq!("[1]", encode(&merge_diff(&a, &parse("[1]").unwrap())));
+}
+
+fn merged(base: &str, overlay: &str, options: &MergeOptions) -> String {
+    let mut v = parse(base).unwrap();
+    v.merge(parse(overlay).unwrap(), options);
+    encode(&v)
+}
+
+const MERGE_BASE: &str = r#"{"a": {"x": 1, "y": [1, 2]}, "b": null, "c": 1}"#;
+const MERGE_OVERLAY: &str = r#"{"a": {"y": [3], "z": true}, "b": 2, "c": null}"#;
+
+#[test]
+fn deep_merge_replaces_arrays() {
+    assert_eq!(
+        r#"{"a":{"x":1,"y":[3],"z":true},"b":2,"c":null}"#,
+        merged(MERGE_BASE, MERGE_OVERLAY, &MergeOptions::default())
+    );
+}
+
+#[test]
+fn deep_merge_concatenates_and_removes_nulls() {
+    let options = MergeOptions {
+        arrays: ArrayMerge::Concatenate,
+        null_removes: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        r#"{"a":{"x":1,"y":[1,2,3],"z":true},"b":2}"#,
+        merged(MERGE_BASE, MERGE_OVERLAY, &options)
+    );
+}
+
+#[test]
+fn deep_merge_arrays_by_index() {
+    let options = MergeOptions {
+        arrays: ArrayMerge::ByIndex,
+        ..Default::default()
+    };
+    assert_eq!(
+        r#"[{"a":1,"b":3},2,3]"#,
+        merged(r#"[{"a": 1, "b": 2}, 2]"#, r#"[{"b": 3}, 2, 3]"#, &options)
+    );
+}
+
+#[test]
+fn deep_merge_arrays_by_key() {
+    let options = MergeOptions {
+        arrays: ArrayMerge::ByKey("name".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(
+        r#"[{"name":"a","v":1},{"name":"b","v":3,"w":4},{"name":"c"},5]"#,
+        merged(
+            r#"[{"name": "a", "v": 1}, {"name": "b", "v": 2}]"#,
+            r#"[{"name": "b", "v": 3, "w": 4}, {"name": "c"}, 5]"#,
+            &options
+        )
+    );
+}
+
+#[test]
+fn deep_merge_array_overrides() {
+    // Layered configuration, with strategies for particular arrays.
+    let options = MergeOptions {
+        arrays: ArrayMerge::Replace,
+        array_overrides: vec![
+            (
+                JsonPointer::parse("/meshes").unwrap(),
+                ArrayMerge::ByKey("name".to_owned()),
+            ),
+            (
+                JsonPointer::parse("/meshes/0/indeces").unwrap(),
+                ArrayMerge::Concatenate,
+            ),
+        ],
+        null_removes: false,
+    };
+    let base = r#"{"meshes": [{"name": "cube", "indeces": [0, 1], "points": [{"x": 0, "y": 0}]},
+        {"name": "quad", "indeces": [2]}]}"#;
+    let platform =
+        r#"{"meshes": [{"name": "cube", "indeces": [2], "points": [{"x": 1, "y": 1}]}]}"#;
+    let user = r#"{"meshes": [{"name": "quad", "indeces": [3], "points": []}]}"#;
+    let v = merge_all(
+        [base, platform, user].iter().map(|s| parse(s).unwrap()),
+        &options,
+    );
+    let mut meshes = BTreeMap::<String, Mesh>::new();
+    for m in v.as_object().unwrap()["meshes"].as_array().unwrap() {
+        let mut mesh = Mesh::default();
+        FromJSON::from_json(m, &mut mesh).unwrap();
+        meshes.insert(
+            m.pointer("/name").unwrap().as_string().unwrap().clone(),
+            mesh,
+        );
+    }
+    assert_eq!(
+        Mesh {
+            points: vec![Point { x: 1.0, y: 1.0 }],
+            indeces: vec![0, 1, 2],
+        },
+        meshes["cube"]
+    );
+    assert_eq!(
+        Mesh {
+            points: vec![],
+            indeces: vec![3],
+        },
+        meshes["quad"]
+    );
+}
+
+#[test]
+fn merge_all_of_nothing_is_null() {
+    assert_eq!(Value::Null, merge_all([], &MergeOptions::default()));
+}
+
+fn select(doc: &Value, query: &str) -> Vec<(String, String)> {
+    let nodes = doc
+        .query(query)
+        .unwrap_or_else(|e| panic!("{}: {}", query, e));
+    nodes
+        .iter()
+        .map(|n| (n.normalized_path(), encode(n.value)))
+        .collect()
+}
+
+fn values(doc: &Value, query: &str) -> Vec<String> {
+    select(doc, query).into_iter().map(|(_, v)| v).collect()
+}
+
+#[test]
+fn jsonpath_rfc_examples() {
+    // Examples from RFC 9535.
+    let store = parse(
+        r#"{"store": {
+            "book": [
+                {"category": "reference", "author": "Nigel Rees", "title": "Sayings of the Century", "price": 8.95},
+                {"category": "fiction", "author": "Evelyn Waugh", "title": "Sword of Honour", "price": 12.99},
+                {"category": "fiction", "author": "Herman Melville", "title": "Moby Dick", "isbn": "0-553-21311-3", "price": 8.99},
+                {"category": "fiction", "author": "J. R. R. Tolkien", "title": "The Lord of the Rings", "isbn": "0-395-19395-8", "price": 22.99}
+            ],
+            "bicycle": {"color": "red", "price": 399}
+        }}"#,
+    )
+    .unwrap();
+    let authors = [
+        r#""Nigel Rees""#,
+        r#""Evelyn Waugh""#,
+        r#""Herman Melville""#,
+        r#""J. R. R. Tolkien""#,
+    ];
+    assert_eq!(authors.to_vec(), values(&store, "$.store.book[*].author"));
+    assert_eq!(authors.to_vec(), values(&store, "$..author"));
+    assert_eq!(
+        vec!["$['store']['book']", "$['store']['bicycle']"],
+        select(&store, "$.store.*")
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["8.95", "12.99", "8.99", "22.99", "399"],
+        values(&store, "$.store..price")
+    );
+    assert_eq!(
+        vec![(
+            "$['store']['book'][2]['title']".to_owned(),
+            r#""Moby Dick""#.to_owned()
+        )],
+        select(&store, "$..book[2].title")
+    );
+    assert_eq!(
+        vec!["$['store']['book'][3]"],
+        select(&store, "$..book[-1]")
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        values(&store, "$..book[0,1]"),
+        values(&store, "$..book[:2]")
+    );
+    assert_eq!(
+        vec![r#""Moby Dick""#, r#""The Lord of the Rings""#],
+        values(&store, "$..book[?@.isbn].title")
+    );
+    assert_eq!(
+        vec![r#""Sayings of the Century""#, r#""Moby Dick""#],
+        values(&store, "$..book[?@.price<10].title")
+    );
+    assert_eq!(27, store.query("$..*").unwrap().len());
+}
+
+fn filter_doc() -> Value {
+    parse(
+        r#"{"a": [3, 5, 1, 2, 4, 6, {"b": "j"}, {"b": "k"}, {"b": {}}, {"b": "kilo"}],
+            "o": {"p": 1, "q": 2, "r": 3, "s": 5, "t": {"u": 6}},
+            "e": "f"}"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn jsonpath_selectors() {
+    let cases: &[(&str, &[&str])] = &[
+        ("$ .o [ 'p' , \"q\" ]", &["$['o']['p']", "$['o']['q']"]),
+        ("$['missing'][0]", &[]),
+        ("$.a.b", &[]),
+        ("$.o[0]", &[]),
+    ];
+    for (query, expected) in cases {
+        let paths: Vec<_> = select(&filter_doc(), query)
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(expected.to_vec(), paths, "{}", query);
+    }
+}
+
+#[test]
+fn jsonpath_slices() {
+    let letters = parse(r#"["a", "b", "c", "d", "e", "f", "g"]"#).unwrap();
+    let slices = [
+        ("$[1:3]", "bc"),
+        ("$[5:]", "fg"),
+        ("$[1:5:2]", "bd"),
+        ("$[5:1:-2]", "fd"),
+        ("$[::-1]", "gfedcba"),
+        ("$[-2:]", "fg"),
+        ("$[:-5:-1]", "gfed"),
+        ("$[0:100:0]", ""),
+        ("$[10:20]", ""),
+    ];
+    for (query, expected) in slices {
+        let joined: String = letters
+            .query(query)
+            .unwrap()
+            .iter()
+            .map(|n| n.value.as_string().unwrap().as_str())
+            .collect();
+        assert_eq!(expected, joined, "{}", query);
+    }
+}
+
+#[test]
+fn jsonpath_filters() {
+    let cases: &[(&str, &[&str])] = &[
+        ("$.a[?@.b == 'kilo']", &["$['a'][9]"]),
+        ("$.a[?(@.b == 'kilo')]", &["$['a'][9]"]),
+        ("$.a[?@>3.5]", &["$['a'][1]", "$['a'][4]", "$['a'][5]"]),
+        (
+            "$.a[?@.b]",
+            &["$['a'][6]", "$['a'][7]", "$['a'][8]", "$['a'][9]"],
+        ),
+        ("$[?@.*]", &["$['a']", "$['o']"]),
+        ("$[?@[?@.b]]", &["$['a']"]),
+        (
+            "$.o[?@<3, ?@<3]",
+            &["$['o']['p']", "$['o']['q']", "$['o']['p']", "$['o']['q']"],
+        ),
+        ("$.a[?@<2 || @.b == \"k\"]", &["$['a'][2]", "$['a'][7]"]),
+        ("$.o[?@>1 && @<4]", &["$['o']['q']", "$['o']['r']"]),
+        ("$.o[?@.u || @.x]", &["$['o']['t']"]),
+        (
+            "$.a[?@.b == $.x]",
+            &[
+                "$['a'][0]",
+                "$['a'][1]",
+                "$['a'][2]",
+                "$['a'][3]",
+                "$['a'][4]",
+                "$['a'][5]",
+            ],
+        ),
+        ("$.a[?!(@ < 5) && !@.b]", &["$['a'][1]", "$['a'][5]"]),
+        (
+            "$.a[?@ == @]",
+            &[
+                "$['a'][0]",
+                "$['a'][1]",
+                "$['a'][2]",
+                "$['a'][3]",
+                "$['a'][4]",
+                "$['a'][5]",
+                "$['a'][6]",
+                "$['a'][7]",
+                "$['a'][8]",
+                "$['a'][9]",
+            ],
+        ),
+    ];
+    for (query, expected) in cases {
+        let paths: Vec<_> = select(&filter_doc(), query)
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(expected.to_vec(), paths, "{}", query);
+    }
+}
+
+#[test]
+fn jsonpath_functions() {
+    let cases: &[(&str, &[&str])] = &[
+        ("$.a[?match(@.b, \"[jk]\")]", &["$['a'][6]", "$['a'][7]"]),
+        (
+            "$.a[?search(@.b, \"[jk]\")]",
+            &["$['a'][6]", "$['a'][7]", "$['a'][9]"],
+        ),
+        ("$[?length(@) < 3]", &["$['e']"]),
+        ("$[?count(@.*) == 5]", &["$['o']"]),
+        ("$.*[?value(@..u) == 6]", &["$['o']['t']"]),
+    ];
+    for (query, expected) in cases {
+        let paths: Vec<_> = select(&filter_doc(), query)
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(expected.to_vec(), paths, "{}", query);
+    }
+}
+
+#[test]
+fn jsonpath_compiled_query() {
+    let query = JsonPath::parse("$.points[?@.x > 5].y").unwrap();
+    assert_eq!("$.points[?@.x > 5].y", query.to_string());
+    let a = parse(r#"{"points": [{"x": 1, "y": 2}, {"x": 6, "y": 7}, {"x": 5.5}]}"#).unwrap();
+    let b = parse(r#"{"points": [{"x": 10, "y": "ten"}]}"#).unwrap();
+    let nodes = query.query(&a);
+    assert_eq!(1, nodes.len());
+    assert_eq!(&Value::Number(Number::from(7)), nodes[0].value);
+    assert_eq!(
+        vec![
+            PathSegment::Key("points".to_owned()),
+            PathSegment::Index(1),
+            PathSegment::Key("y".to_owned())
+        ],
+        nodes[0].path
+    );
+    assert_eq!("/points/1/y", nodes[0].pointer().to_string());
+    assert_eq!(vec![&Value::String("ten".into())], query.query_values(&b));
+}
+
+#[test]
+fn jsonpath_normalized_paths_escape_names() {
+    let odd = parse(r#"{"a'b\\\n\u0001": 1}"#).unwrap();
+    assert_eq!(
+        vec![(r#"$['a\'b\\\n\u0001']"#.to_owned(), "1".to_owned())],
+        select(&odd, "$.*")
+    );
+    assert_eq!(vec!["1"], values(&odd, r#"$["a'b\\\n\u0001"]"#));
+}
+
+#[test]
+fn jsonpath_iregexp() {
+    let words =
+        parse(r#"["abc", "aXc", "a\nc", "aac", "abab", "Hello", "hello", "x-y", "ab"]"#).unwrap();
+    let regex_cases = [
+        ("match(@, 'a.c')", vec!["abc", "aXc", "aac"]),
+        ("match(@, 'a{2}c')", vec!["aac"]),
+        ("match(@, '(ab)+')", vec!["abab", "ab"]),
+        ("match(@, '[^a-z]ello')", vec!["Hello"]),
+        ("match(@, '\\\\p{Lu}\\\\p{Ll}+')", vec!["Hello"]),
+        ("match(@, '[a-z\\\\-]+') && search(@, '-')", vec!["x-y"]),
+        (
+            "search(@, 'b') && !match(@, 'b')",
+            vec!["abc", "abab", "ab"],
+        ),
+        ("match(@, 'ab|x.y')", vec!["x-y", "ab"]),
+        ("match(@, $[0])", vec!["abc"]),
+    ];
+    for (filter, expected) in regex_cases {
+        let query = format!("$[?{}]", filter);
+        let found: Vec<_> = words
+            .query(&query)
+            .unwrap()
+            .iter()
+            .map(|n| n.value.as_string().unwrap().clone())
+            .collect();
+        assert_eq!(expected, found, "{}", query);
+    }
+}
+
+#[test]
+fn jsonpath_iregexp_categories() {
+    let chars = [
+        "A", "a", "\u{1C5}", "\u{2B0}", "中", "\u{301}", "5", "\u{2160}", "½", "_", "-", "(", ")",
+        "«", "»", "!", " ", "+", "$", "^", "©", "\u{7}", "\u{AD}", "\u{E000}", "\u{378}",
+    ];
+    let chars = Value::Array(chars.iter().map(|&c| Value::from(c)).collect());
+    let cases = [
+        ("Lu", vec!["A"]),
+        ("Ll", vec!["a"]),
+        ("Lt", vec!["\u{1C5}"]),
+        ("Lm", vec!["\u{2B0}"]),
+        ("Lo", vec!["中"]),
+        ("L", vec!["A", "a", "\u{1C5}", "\u{2B0}", "中"]),
+        ("M", vec!["\u{301}"]),
+        ("Nd", vec!["5"]),
+        // Roman numerals are alphabetic and uppercase, but they are numbers, not letters.
+        ("Nl", vec!["\u{2160}"]),
+        ("No", vec!["½"]),
+        ("N", vec!["5", "\u{2160}", "½"]),
+        ("Pc", vec!["_"]),
+        ("Pd", vec!["-"]),
+        ("Ps", vec!["("]),
+        ("Pe", vec![")"]),
+        ("Pi", vec!["«"]),
+        ("Pf", vec!["»"]),
+        ("Po", vec!["!"]),
+        ("Zs", vec![" "]),
+        ("Sm", vec!["+"]),
+        ("Sc", vec!["$"]),
+        ("Sk", vec!["^"]),
+        ("So", vec!["©"]),
+        ("Cc", vec!["\u{7}"]),
+        ("Cf", vec!["\u{AD}"]),
+        ("Co", vec!["\u{E000}"]),
+        ("Cn", vec!["\u{378}"]),
+        ("C", vec!["\u{7}", "\u{AD}", "\u{E000}", "\u{378}"]),
+    ];
+    for (category, expected) in cases {
+        let query = format!("$[?match(@, '\\\\p{{{}}}')]", category);
+        let found: Vec<_> = chars
+            .query(&query)
+            .unwrap()
+            .iter()
+            .map(|n| n.value.as_string().unwrap().as_str())
+            .collect();
+        assert_eq!(expected, found, "{}", query);
+        let query = format!("$[?match(@, '[^\\\\P{{{}}}]')]", category);
+        assert_eq!(
+            expected.len(),
+            chars.query(&query).unwrap().len(),
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn jsonpath_iregexp_rejected() {
+    // An invalid pattern matches nothing, and its negation matches everything.  search would
+    // find something in the words with each of these if it were accepted.
+    let words = parse(r#"["a+b", "$5", "(a", "aa", "b-a"]"#).unwrap();
+    let patterns = [
+        // RFC 9485 leaves out surrogates, and only has the general categories.
+        "\\\\P{Cs}",
+        "\\\\P{Sx}",
+        "\\\\p{Lx}",
+        "[\\\\P{Greek}]",
+        "(a",
+        "a)",
+        "a{2,1}",
+        "[b-a]",
+        "a**",
+        "\\\\d",
+    ];
+    for pattern in patterns {
+        for function in ["match", "search"] {
+            let query = format!("$[?{}(@, '{}')]", function, pattern);
+            assert!(words.query(&query).unwrap().is_empty(), "{}", query);
+            let query = format!("$[?!{}(@, '{}')]", function, pattern);
+            assert_eq!(5, words.query(&query).unwrap().len(), "{}", query);
+        }
+    }
+    // Patterns that come from the document are checked when the query runs.
+    let doc = parse(r#"{"p": "\\p{Xx}", "q": "\\p{N}", "w": "$5"}"#).unwrap();
+    assert!(values(&doc, "$[?search(@, $.p)]").is_empty());
+    assert_eq!(vec![r#""$5""#], values(&doc, "$[?search(@, $.q)]"));
+}
+
+#[test]
+fn jsonpath_iregexp_linear_time() {
+    // Matching takes linear time, even for patterns that backtracking would explode on.
+    let long = Value::Array(vec![Value::String("a".repeat(10000))]);
+    assert!(long.query("$[?match(@, '(a*)*b')]").unwrap().is_empty());
+}
+
+#[test]
+fn jsonpath_parse_errors() {
+    let errors = [
+        ("", 0),
+        (" $", 0),
+        ("$ ", 1),
+        ("$.a ", 3),
+        ("$. a", 2),
+        ("$.. a", 3),
+        ("$[01]", 2),
+        ("$[-0]", 2),
+        ("$[9007199254740992]", 2),
+        ("$['a'", 5),
+        ("$['\\x']", 3),
+        ("$['\\ud800']", 3),
+        ("$[?@.a == 01]", 10),
+        ("$[?@.a == 1.]", 10),
+        ("$[?@.* == 1]", 3),
+        ("$[?@..a == 1]", 3),
+        ("$[?length(@.*) < 3]", 10),
+        ("$[?count(1) == 1]", 9),
+        ("$[?match(@.a, 'x') == true]", 3),
+        ("$[?length(@)]", 3),
+        ("$[?1]", 3),
+        ("$[?foo(@)]", 3),
+        ("$[?length(@, 1) == 1]", 3),
+        ("$[?@.a == 1 == 2]", 12),
+        ("$[?@ = 1]", 5),
+        ("$[?!@.a == 1]", 8),
+        ("$[1 2]", 4),
+    ];
+    for (query, offset) in errors {
+        let err = JsonPath::parse(query).unwrap_err();
+        assert_eq!(offset, err.offset, "{}: {}", query, err);
+    }
+    assert_eq!(
+        "value must be compared at offset 3",
+        JsonPath::parse("$[?length(@)]").unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn jsonpath_nesting_limit() {
+    let deep = format!("$[?{}@{}]", "(".repeat(100), ")".repeat(100));
+    assert!(JsonPath::parse(&deep).is_err());
+}
+
+#[test]
+fn value_from_rust_values() {
+    let v: Value = [
+        ("name", Value::from("cube")),
+        ("visible", true.into()),
+        ("scale", 1.5.into()),
+        ("points", vec![1u32, 2, 3].into()),
+        ("parent", None::<String>.into()),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(
+        r#"{"name":"cube","visible":true,"scale":1.5,"points":[1,2,3],"parent":null}"#,
+        encode(&v)
+    );
+    let squares: Value = (0..4i64).map(|i| i * i).collect();
+    assert_eq!("[0,1,4,9]", encode(&squares));
+    assert_eq!(Value::Null, Value::default());
+
+    // bool is FromJSON and ToJSON.
+    assert_eq!(Ok(vec![true, false]), decode::<Vec<bool>>("[true, false]"));
+    assert_eq!("[true]", encode(&vec![true]));
+}
+
+fn cube() -> Value {
+    parse(r#"{"name":"cube","visible":true,"scale":1.5,"points":[1,2,3],"parent":null}"#).unwrap()
+}
+
+#[test]
+fn value_accessors() {
+    let v = cube();
+    assert!(v.is_object() && !v.is_array());
+    assert!(v["parent"].is_null());
+    assert!(v["name"].is_string() && v["visible"].is_boolean() && v["scale"].is_number());
+    assert!(v["points"].is_array());
+    assert_eq!(Ok(true), v["visible"].as_bool());
+    assert_eq!(
+        Err(DecodeError::expected("boolean", &v["name"])),
+        v["name"].as_bool()
+    );
+    assert_eq!(Some(&Value::from(2u32)), v["points"].get(1));
+    assert_eq!(None, v["points"].get(3));
+    assert_eq!(None, v.get("missing"));
+    assert_eq!(None, v.get(0));
+    let key = "name".to_owned();
+    assert_eq!(Some(&Value::from("cube")), v.get(&key));
+}
+
+#[test]
+fn value_editing_in_place() {
+    let mut v = cube();
+    *v["visible"].as_bool_mut().unwrap() = false;
+    v["name"].as_string_mut().unwrap().push_str("-2");
+    *v["scale"].as_number_mut().unwrap() = Number::from(2);
+    v["points"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|p| p != &Value::from(2u32));
+    v["points"][0] = Value::from(10u32);
+    *v.get_mut("parent").unwrap() = "root".into();
+    v.as_object_mut().unwrap().sort_keys();
+    assert_eq!(
+        r#"{"name":"cube-2","parent":"root","points":[10,3],"scale":2,"visible":false}"#,
+        encode(&v)
+    );
+    assert!(v.as_array_mut().is_err());
+}
+
+#[test]
+fn value_insert_push_remove() {
+    let mut v = cube();
+    assert_eq!(Ok(Some(Value::from(true))), v.insert("visible", false));
+    assert_eq!(Ok(None), v.insert("tags", Value::Null));
+    assert_eq!(Ok(()), v["tags"].push("a"));
+    assert_eq!(Ok(()), v["tags"].push(vec!["b", "c"]));
+    assert_eq!(r#"["a",["b","c"]]"#, encode(&v["tags"]));
+    assert_eq!(Some(Value::from(1u32)), v["points"].remove(0));
+    assert_eq!(None, v["points"].remove(5));
+    assert_eq!(Some(Value::Null), v.remove("parent"));
+    assert_eq!(None, v.remove("parent"));
+
+    let points = v["points"].take();
+    assert_eq!("[2,3]", encode(&points));
+    assert!(v["points"].is_null());
+    assert_eq!(
+        r#"{"name":"cube","visible":false,"scale":1.5,"points":null,"tags":["a",["b","c"]]}"#,
+        encode(&v)
+    );
+}
+
+#[test]
+fn value_insert_push_wrong_kind() {
+    let mut v = Value::from(1u32);
+    assert_eq!(Err(DecodeError::expected("array", &v)), v.push(2u32));
+    assert_eq!(
+        Err(DecodeError::expected("object", &v)),
+        v.insert("a", 2u32)
+    );
+    assert_eq!(Value::from(1u32), v);
+
+    let mut a = Value::from(vec![1u32]);
+    assert_eq!(
+        "$: expected object, got array",
+        a.insert("a", 2u32).unwrap_err().to_string()
+    );
+    let mut o = cube();
+    assert!(o.push(2u32).is_err());
+    assert_eq!(cube(), o);
+}
+
+#[test]
+#[should_panic(expected = "\"missing\" not present in object")]
+fn value_index_missing_key() {
+    let v: Value = [("a", 1u32)].into_iter().collect();
+    let _ = &v["missing"];
+}
+
+#[test]
+fn value_index_assign_inserts() {
+    let mut v = Value::Null;
+    v["name"] = "cube".into();
+    v["size"]["x"] = 1u32.into();
+    v["size"]["y"] = 2u32.into();
+    v["size"]["x"] = 3u32.into();
+    v["tags"].push("a").unwrap();
+    assert_eq!(
+        r#"{"name":"cube","size":{"x":3,"y":2},"tags":["a"]}"#,
+        encode(&v)
+    );
+    // Only assigning adds anything.
+    assert_eq!(None, v.get("missing"));
+}
+
+#[test]
+#[should_panic(expected = "\"a\" not present in array")]
+fn value_index_assign_wrong_kind() {
+    let mut v = Value::from(vec![1u32]);
+    v["a"] = Value::Null;
+}
+
+#[test]
+#[should_panic(expected = "1 not present in array")]
+fn value_index_assign_out_of_bounds() {
+    let mut v = Value::from(vec![1u32]);
+    v[1] = Value::Null;
+}